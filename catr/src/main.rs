@@ -1,8 +1,12 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, IsTerminal, Lines, Write};
+use std::os::fd::AsFd;
+use unicode_width::UnicodeWidthChar;
+
+mod pager;
 
 
 #[derive(Debug, Parser)]
@@ -39,6 +43,60 @@ struct Args {
     /// use ^ and M- notation, except for LFD and TAB
     #[arg(short('v'), long("show-nonprinting"))]
     show_nonprinting: bool,
+    /// show the files in adjacent columns
+    #[arg(long("side-by-side"), conflicts_with("interleave"))]
+    side_by_side: bool,
+    /// alternate lines from each file, like paste -d '\n'
+    #[arg(long("interleave"))]
+    interleave: bool,
+    /// column width for --side-by-side
+    #[arg(value_name("COLS"),
+          long("width"),
+          default_value("40"),
+          value_parser(clap::value_parser!(u64).range(1..))
+    )]
+    width: u64,
+    /// column separator for --side-by-side
+    #[arg(value_name("SEP"), long("separator"), default_value(" | "))]
+    separator: String,
+    /// page output that is taller than the terminal through $PAGER or the
+    /// built-in pager; never applies when stdout is not a terminal
+    #[arg(value_name("WHEN"), long("paging"), value_enum, default_value("auto"))]
+    paging: Paging,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Paging {
+    Auto,
+    Always,
+    Never,
+}
+
+// Line source for one file in --side-by-side and --interleave mode, with its
+// own numbering and blank line state so each column behaves like a plain cat
+// of that file.
+struct Column {
+    lines: Lines<Box<dyn BufRead>>,
+    count: usize,
+    last_blank: bool,
+}
+
+impl Column {
+    fn new(source: Box<dyn BufRead>) -> Column {
+        Column { lines: source.lines(), count: 1, last_blank: false }
+    }
+
+    fn next_line(&mut self, args: &Args) -> Result<Option<String>> {
+        // Return the next formatted line, skipping squeezed blank lines, or
+        // None once the file is exhausted.
+        for line in self.lines.by_ref() {
+            if let Some(line) = format_line(args, line?, &mut self.count,
+                                            &mut self.last_blank) {
+                return Ok(Some(line));
+            }
+        }
+        Ok(None)
+    }
 }
 
 fn open(filename: &str) -> Result<Box<dyn BufRead>> {
@@ -74,7 +132,131 @@ fn show_nonprinting_chars(line: String) -> String {
     converted
 }
 
-fn run(mut args: Args) -> Result<()> {
+fn format_line(args: &Args, line: String, count: &mut usize,
+               last_blank: &mut bool) -> Option<String> {
+    // Apply the display flags to a single line. Returns None when the line
+    // is suppressed by -s.
+    let mut line = if args.show_tabs {line.replace('\t', "^I")} else {line};
+
+    if args.show_nonprinting {
+        line = show_nonprinting_chars(line);
+    }
+
+    // if blank line suppression set and the line is empty,
+    // skip printing if last line was empty
+    if args.squeeze_blank && line.is_empty() && *last_blank {
+        return None;
+    }
+    // set variable for multiple blank line suppression based
+    // on current line contents.
+    *last_blank = line.is_empty();
+
+    // process line numbering if either flag is set.
+    let number = if args.number_lines
+                    || (args.number_nonblank_lines && !line.is_empty()) {
+        *count += 1;
+        format!("{:>6}\t", *count - 1)
+    } else {
+        String::new()
+    };
+
+    // add the endline character, depending on flag.
+    Some(format!("{number}{line}{}", if args.show_ends {"$"} else {""}))
+}
+
+fn fit_column(line: &str, width: usize, marker: &str, pad: bool) -> String {
+    // Expand tabs to 8-column stops so the columns line up, then cut the
+    // result to `width` display columns, and pad it out to exactly that
+    // if asked. Wide characters take two columns, and one that would
+    // straddle the edge is left out. The -E marker goes after the cut so it
+    // survives on long lines.
+    let room = width.saturating_sub(marker.len());
+    let mut cell = String::new();
+    let mut used = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let stop = (used / 8 + 1) * 8;
+            while used < stop.min(room) {
+                cell.push(' ');
+                used += 1;
+            }
+            if used == room {
+                break;
+            }
+            continue;
+        }
+        let cols = c.width().unwrap_or(0);
+        if used + cols > room {
+            break;
+        }
+        cell.push(c);
+        used += cols;
+    }
+    cell.push_str(marker);
+    used += marker.len().min(width);
+    if pad {
+        cell.extend(std::iter::repeat_n(' ', width - used));
+    }
+    cell
+}
+
+fn run_columns(args: &Args, out: &mut impl Write) -> Result<()> {
+    // Read one line from every file per round and print the round either
+    // as a single row (--side-by-side) or as consecutive lines
+    // (--interleave). Files that run out early are padded with blanks until
+    // the longest one is done.
+    let mut columns = Vec::new();
+    for filename in &args.files {
+        match open(filename) {
+            Err(err) => eprintln!("Failed to open {filename}: {err}"),
+            Ok(source) => columns.push(Column::new(source)),
+        }
+    }
+
+    let width = args.width as usize;
+    loop {
+        let mut row = Vec::with_capacity(columns.len());
+        for column in columns.iter_mut() {
+            row.push(column.next_line(args)?);
+        }
+        if row.iter().all(Option::is_none) {
+            break;
+        }
+
+        if args.interleave {
+            for line in row {
+                writeln!(out, "{}", line.unwrap_or_default())?;
+            }
+        } else {
+            let last = row.len() - 1;
+            let cells: Vec<String> = row
+                .into_iter()
+                .enumerate()
+                .map(|(i, line)| {
+                    // fit the line without its -E marker, then put the
+                    // marker back on the end. The last column isn't padded,
+                    // so rows don't end in blanks that aren't in the file.
+                    let line = line.unwrap_or_default();
+                    let (line, marker) = match line.strip_suffix('$') {
+                        Some(text) if args.show_ends => (text, "$"),
+                        _ => (line.as_str(), ""),
+                    };
+                    fit_column(line, width, marker, i != last)
+                })
+                .collect();
+            let mut line = cells.join(&args.separator);
+            // nor in the blanks of a separator with nothing after it.
+            if cells[last].is_empty() && line.ends_with(&args.separator) {
+                let sep = &args.separator;
+                line.truncate(line.len() - (sep.len() - sep.trim_end().len()));
+            }
+            writeln!(out, "{line}")?;
+        }
+    }
+    Ok(())
+}
+
+fn run(mut args: Args, out: &mut impl Write) -> Result<()> {
     // Output file contents based on information received from command-line
 
     // initialize variable to track whether the last line was blank
@@ -97,41 +279,22 @@ fn run(mut args: Args) -> Result<()> {
         args.show_tabs = true;       
     }
 
-    for filename in args.files {
-        match open(&filename) {
+    if args.side_by_side || args.interleave {
+        return run_columns(&args, out);
+    }
+
+    for filename in &args.files {
+        match open(filename) {
             Err(err) => eprintln!("Failed to open {filename}: {err}"),
             Ok(source) => {
                 // initialize variable for line numbering
                 let mut count = 1;
 
                 for line in source.lines() {
-                    let mut line = if args.show_tabs {line?.replace('\t', "^I")}
-                               else {line?};
-
-                    if args.show_nonprinting {
-                        line = show_nonprinting_chars(line);
-                    }
-
-                    // if blank line suppression set and the line is empty,
-                    // skip printing if last line was empty
-                    if args.squeeze_blank && line.is_empty() && last_blank {
-                        // ... 
-                        continue;
+                    if let Some(line) = format_line(&args, line?, &mut count,
+                                                    &mut last_blank) {
+                        writeln!(out, "{line}")?;
                     }
-
-                    // process line numbering if either flag is set.
-                    if args.number_lines
-                       || (args.number_nonblank_lines && !line.is_empty()) {
-                            print!("{count:>6}\t");
-                            count += 1;
-                    }
-
-                    // print line with or without endline charaacter, depending
-                    // on flag.
-                    println!("{}{}", line, if args.show_ends {"$"} else {""});
-                    // set variable for multiple blank line suppression based
-                    // on current line contents.
-                    if line.is_empty() {last_blank = true} else {last_blank = false};
                 }
             },
         }
@@ -139,8 +302,49 @@ fn run(mut args: Args) -> Result<()> {
     Ok(())
 }
 
+fn bounded(filename: &str) -> bool {
+    // Whether input is sure to come to an end: regular files are, while
+    // terminals and pipes may go on for ever. Files that can't be opened
+    // add nothing.
+    let metadata = match filename {
+        "-" => io::stdin().as_fd().try_clone_to_owned()
+            .and_then(|fd| File::from(fd).metadata()),
+        _ => fs::metadata(filename),
+    };
+    match metadata {
+        Ok(metadata) => metadata.is_file(),
+        Err(_) => filename != "-",
+    }
+}
+
+fn stopped_early(result: Result<()>) -> Result<()> {
+    // The pager quitting before the end means the user has seen enough,
+    // so stopping there isn't an error.
+    match result {
+        Err(e) if e.downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) => Ok(()),
+        other => other,
+    }
+}
+
 fn main() {
-    if let Err(e) = run(Args::parse()) {
+    let args = Args::parse();
+    let paging = args.paging;
+    let result = if paging == Paging::Never || !io::stdout().is_terminal() {
+        run(args, &mut io::stdout().lock())
+    } else {
+        // Whatever run produced still has to reach the terminal, even if it
+        // stopped on an error part way through.
+        let bounded = args.files.iter().all(|f| bounded(f));
+        match pager::Pager::new(paging == Paging::Always, bounded) {
+            Err(e) => stopped_early(Err(e.into())),
+            Ok(mut out) => {
+                let result = stopped_early(run(args, &mut out));
+                result.and(out.finish())
+            }
+        }
+    };
+    if let Err(e) = result {
         eprintln!("{e}");
         std::process::exit(1);
     }
@@ -0,0 +1,294 @@
+use anyhow::Result;
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::fd::AsRawFd;
+use std::process::{Child, Command, Stdio};
+
+
+/// Output sink that pages catr's output when it is taller than the terminal.
+/// With --paging=auto, output from bounded input is held back until it is
+/// clear whether it fits: short output is written to stdout as-is, anything
+/// taller goes to `$PAGER`, or to the built-in pager when `$PAGER` is unset.
+/// Output from input that may never end, like a terminal or `tail -f`, is
+/// streamed straight through, since the built-in pager would have to wait
+/// for all of it.
+///
+/// Once `$PAGER` has quit, writes fail with `BrokenPipe`, which is the
+/// signal to stop reading input.
+pub struct Pager {
+    sink: Sink,
+    rows: usize,
+}
+
+enum Sink {
+    /// Not decided yet; everything so far and how many lines it has.
+    Holding(Vec<u8>, usize),
+    Stdout(io::Stdout),
+    Child(Child),
+    /// Everything so far, for the built-in pager to show at the end.
+    Builtin(Vec<u8>),
+}
+
+impl Pager {
+    /// `bounded` says whether all the input is known to come to an end.
+    pub fn new(always: bool, bounded: bool) -> io::Result<Pager> {
+        let mut pager = Pager {
+            sink: Sink::Holding(Vec::new(), 0),
+            rows: terminal_size().0,
+        };
+        if always {
+            pager.start(bounded)?;
+        } else if !bounded {
+            pager.sink = Sink::Stdout(io::stdout());
+        }
+        Ok(pager)
+    }
+
+    fn start(&mut self, bounded: bool) -> io::Result<()> {
+        // Hand everything held back so far to $PAGER and stream the rest to
+        // it. Without a usable $PAGER, keep buffering for the built-in one,
+        // as long as the input will come to an end.
+        let Sink::Holding(buffer, _) = &mut self.sink else {
+            return Ok(());
+        };
+        let buffer = std::mem::take(buffer);
+        if let Some(pager) = env::var("PAGER").ok().filter(|p| !p.trim().is_empty()) {
+            if let Ok(mut child) = Command::new("sh")
+                .args(["-c", &pager])
+                .stdin(Stdio::piped())
+                .spawn()
+            {
+                let stdin = child.stdin.as_mut().expect("piped stdin");
+                let written = stdin.write_all(&buffer);
+                self.sink = Sink::Child(child);
+                return written;
+            }
+        }
+        if bounded {
+            self.sink = Sink::Builtin(buffer);
+        } else {
+            let mut stdout = io::stdout();
+            stdout.write_all(&buffer)?;
+            self.sink = Sink::Stdout(stdout);
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<()> {
+        // Flush whatever is left to its final destination. $PAGER having
+        // quit before the end is fine; the user has seen enough.
+        match self.sink {
+            Sink::Holding(buffer, _) => io::stdout().write_all(&buffer)?,
+            Sink::Stdout(mut stdout) => stdout.flush()?,
+            Sink::Child(mut child) => {
+                drop(child.stdin.take());
+                child.wait()?;
+            }
+            Sink::Builtin(buffer) if !buffer.is_empty() => browse(&buffer)?,
+            Sink::Builtin(_) => {}
+        }
+        Ok(())
+    }
+}
+
+impl Write for Pager {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        match &mut self.sink {
+            Sink::Holding(buffer, lines) => {
+                buffer.extend_from_slice(data);
+                *lines += data.iter().filter(|&&b| b == b'\n').count();
+                if *lines >= self.rows {
+                    self.start(true)?;
+                }
+            }
+            Sink::Stdout(stdout) => stdout.write_all(data)?,
+            Sink::Child(child) => {
+                child.stdin.as_mut().expect("piped stdin").write_all(data)?
+            }
+            Sink::Builtin(buffer) => buffer.extend_from_slice(data),
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.sink {
+            Sink::Stdout(stdout) => stdout.flush(),
+            Sink::Child(child) => child.stdin.as_mut().expect("piped stdin").flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn terminal_size() -> (usize, usize) {
+    // Rows and columns of the terminal on stdout, falling back to $LINES,
+    // $COLUMNS and then 24x80.
+    let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) } == 0
+        && ws.ws_row > 0
+        && ws.ws_col > 0
+    {
+        return (ws.ws_row as usize, ws.ws_col as usize);
+    }
+    let from_env = |name: &str, default: usize| {
+        env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+    };
+    (from_env("LINES", 24), from_env("COLUMNS", 80))
+}
+
+
+// Puts the controlling terminal into non-canonical, no-echo mode for the
+// lifetime of the guard so single keystrokes can be read.
+struct RawMode {
+    tty: File,
+    saved: libc::termios,
+}
+
+impl RawMode {
+    fn enable(tty: File) -> io::Result<RawMode> {
+        let fd = tty.as_raw_fd();
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut raw = saved;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(RawMode { tty, saved })
+    }
+
+    fn key(&mut self) -> io::Result<u8> {
+        let mut byte = [0];
+        self.tty.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSANOW, &self.saved) };
+    }
+}
+
+
+fn browse(text: &[u8]) -> io::Result<()> {
+    // Minimal interactive pager:
+    //   space/f  next page       b        previous page
+    //   enter/j  next line       k        previous line
+    //   g        top             G        bottom
+    //   /text    search forward  n / N    next / previous match
+    //   q        quit
+    let (rows, cols) = terminal_size();
+    let page = rows.saturating_sub(1).max(1);
+
+    // Split the output into screen rows, wrapping long lines at the
+    // terminal width so scrolling always moves by what is on screen.
+    let text = String::from_utf8_lossy(text);
+    let mut screen_rows: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let chars: Vec<char> = line.chars().collect();
+        if chars.is_empty() {
+            screen_rows.push(String::new());
+        }
+        for chunk in chars.chunks(cols.max(1)) {
+            screen_rows.push(chunk.iter().collect());
+        }
+    }
+    let bottom = screen_rows.len().saturating_sub(page);
+
+    let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    let mut raw = RawMode::enable(tty)?;
+    let mut out = io::stdout().lock();
+    let mut top = 0;
+    let mut pattern = String::new();
+    let mut status = String::new();
+
+    // Switch to the alternate screen so quitting restores the shell view.
+    write!(out, "\x1b[?1049h")?;
+    loop {
+        write!(out, "\x1b[H\x1b[2J")?;
+        for row in screen_rows.iter().skip(top).take(page) {
+            writeln!(out, "{row}")?;
+        }
+        if status.is_empty() {
+            write!(out, "{}", if top >= bottom {"(END)"} else {":"})?;
+        } else {
+            write!(out, "{status}")?;
+            status.clear();
+        }
+        out.flush()?;
+
+        let find = |from: usize, forward: bool, pattern: &str| {
+            if forward {
+                (from..screen_rows.len()).find(|&i| screen_rows[i].contains(pattern))
+            } else {
+                (0..from).rev().find(|&i| screen_rows[i].contains(pattern))
+            }
+        };
+
+        let key = raw.key()?;
+        match key {
+            b'q' | b'Q' => break,
+            b' ' | b'f' => top = (top + page).min(bottom),
+            b'b' => top = top.saturating_sub(page),
+            b'\n' | b'\r' | b'j' => top = (top + 1).min(bottom),
+            b'k' => top = top.saturating_sub(1),
+            b'g' => top = 0,
+            b'G' => top = bottom,
+            b'/' => {
+                // Read the search text, echoing it on the status line.
+                let mut typed = Vec::new();
+                write!(out, "\r\x1b[K/")?;
+                out.flush()?;
+                loop {
+                    match raw.key()? {
+                        b'\n' | b'\r' => break,
+                        0x7f | 0x08 => {
+                            typed.pop();
+                        }
+                        0x1b => {
+                            typed.clear();
+                            break;
+                        }
+                        c => typed.push(c),
+                    }
+                    write!(out, "\r\x1b[K/{}", String::from_utf8_lossy(&typed))?;
+                    out.flush()?;
+                }
+                pattern = String::from_utf8_lossy(&typed).into_owned();
+                if !pattern.is_empty() {
+                    match find(top + 1, true, &pattern) {
+                        Some(i) => top = i.min(bottom),
+                        None => status = "Pattern not found".to_string(),
+                    }
+                }
+            }
+            b'n' | b'N' if !pattern.is_empty() => {
+                let found = if key == b'n' {
+                    find(top + 1, true, &pattern)
+                } else {
+                    find(top, false, &pattern)
+                };
+                match found {
+                    Some(i) => top = i.min(bottom),
+                    None => status = "Pattern not found".to_string(),
+                }
+            }
+            // Arrow keys arrive as ESC [ A/B, page keys as ESC [ 5/6 ~.
+            0x1b if raw.key()? == b'[' => match raw.key()? {
+                b'A' => top = top.saturating_sub(1),
+                b'B' => top = (top + 1).min(bottom),
+                b'5' if raw.key()? == b'~' => top = top.saturating_sub(page),
+                b'6' if raw.key()? == b'~' => top = (top + page).min(bottom),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    write!(out, "\x1b[?1049l")?;
+    out.flush()
+}
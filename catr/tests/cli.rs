@@ -10,6 +10,7 @@ const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const SPIDERS: &str = "tests/inputs/spiders.txt";
 const BUSTLE: &str = "tests/inputs/the-bustle.txt";
+const WIDE: &str = "tests/inputs/wide.txt";
 
 // --------------------------------------------------
 #[test]
@@ -201,3 +202,125 @@ fn all_n() -> Result<()> {
 fn all_b() -> Result<()> {
     run(&[FOX, SPIDERS, BUSTLE, "-b"], "tests/expected/all.b.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn dies_side_by_side_and_interleave() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--side-by-side", "--interleave", FOX, SPIDERS])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the argument '--side-by-side' cannot be used with '--interleave'",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn all_side_by_side() -> Result<()> {
+    run(
+        &["--side-by-side", FOX, SPIDERS, BUSTLE],
+        "tests/expected/all.side-by-side.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn all_side_by_side_n_e() -> Result<()> {
+    run(
+        &[
+            "--side-by-side",
+            "-n",
+            "-E",
+            "--width",
+            "20",
+            "--separator",
+            "|",
+            FOX,
+            SPIDERS,
+            BUSTLE,
+        ],
+        "tests/expected/all.side-by-side.nE.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn side_by_side_wide_chars() -> Result<()> {
+    // Columns are measured in display width, so double-width characters
+    // don't push the separators out of line.
+    Command::cargo_bin(PRG)?
+        .args(["--side-by-side", "--width", "7", WIDE, FOX])
+        .assert()
+        .success()
+        .stdout("日本語  | The qui\nwide 表 |\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn all_interleave() -> Result<()> {
+    run(
+        &["--interleave", FOX, SPIDERS, BUSTLE],
+        "tests/expected/all.interleave.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn all_interleave_n_e() -> Result<()> {
+    run(
+        &["--interleave", "-n", "-E", FOX, SPIDERS, BUSTLE],
+        "tests/expected/all.interleave.nE.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_paging() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--paging", "sometimes", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value 'sometimes' for '--paging <WHEN>'",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_paging_always_piped() -> Result<()> {
+    // Paging only ever happens on a terminal, so piped output is unchanged.
+    run(
+        &["--paging", "always", "-n", BUSTLE],
+        "tests/expected/the-bustle.txt.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_paging_auto_piped() -> Result<()> {
+    run(&["--paging=auto", BUSTLE], "tests/expected/the-bustle.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn stops_when_pager_quits() -> Result<()> {
+    // Endless input must not keep catr running once $PAGER has exited.
+    // Paging needs a terminal, which script(1) provides where it exists.
+    if !std::path::Path::new("/usr/bin/script").exists() {
+        return Ok(());
+    }
+    let catr = assert_cmd::cargo::cargo_bin(PRG);
+    let command = format!("yes | {} --paging=always", catr.display());
+    Command::new("/usr/bin/script")
+        .args(["-qec", &command, "/dev/null"])
+        .env("PAGER", "head -n1")
+        .timeout(std::time::Duration::from_secs(20))
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("y"));
+    Ok(())
+}
@@ -0,0 +1,285 @@
+use anyhow::Result;
+use headr::print_header;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::time::Duration;
+
+
+/// How to keep reading files once their tails have been printed.
+pub struct Options {
+    /// Reopen the file name when it is replaced, instead of sticking with
+    /// the file that was opened first (--follow=name).
+    pub by_name: bool,
+    /// Keep trying to open files that are, or become, inaccessible.
+    pub retry: bool,
+    /// How long to wait between checks when there are no inotify events.
+    pub interval: Duration,
+    /// Stop once this process has exited.
+    pub pid: Option<i32>,
+    /// Print `==> name <==` when output switches to another file.
+    pub headers: bool,
+}
+
+
+/// One file being followed.
+pub struct Followed {
+    name: String,
+    file: Option<File>,
+    /// How far into the file we have printed.
+    pos: u64,
+    /// Device and inode of the open file, to notice when the name has
+    /// been given to a different file.
+    id: Option<(u64, u64)>,
+    /// Given up on for good.
+    gone: bool,
+}
+
+impl Followed {
+    /// A file that has been printed up to `pos`.
+    pub fn open(name: &str, file: File, pos: u64) -> Result<Followed> {
+        Ok(Followed {
+            name: name.to_string(),
+            id: Some(identity(&file.metadata()?)),
+            file: Some(file),
+            pos,
+            gone: false,
+        })
+    }
+
+    /// A file that couldn't be opened but may turn up later (--retry).
+    pub fn missing(name: &str) -> Followed {
+        Followed {
+            name: name.to_string(),
+            file: None,
+            pos: 0,
+            id: None,
+            gone: false,
+        }
+    }
+}
+
+
+fn identity(meta: &Metadata) -> (u64, u64) {
+    (meta.dev(), meta.ino())
+}
+
+
+fn process_alive(pid: i32) -> bool {
+    // Signal 0 checks that the process exists without disturbing it.
+    let result = unsafe { libc::kill(pid, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+
+/// Print whatever gets appended to `files` until the --pid process exits or
+/// there is nothing left to follow. `last` is the index of the file whose
+/// output was printed most recently.
+pub fn follow(mut files: Vec<Followed>, options: &Options, mut last: usize)
+    -> Result<()> {
+    let mut watcher = Watcher::new();
+    for followed in &files {
+        watcher.watch(&followed.name);
+    }
+
+    loop {
+        // Check the process before reading so that anything it wrote just
+        // before exiting still gets printed.
+        let finished = options.pid.is_some_and(|pid| !process_alive(pid));
+
+        for (index, followed) in files.iter_mut().enumerate() {
+            if followed.gone {
+                continue;
+            }
+            if options.by_name && followed.name != "-" {
+                check_name(followed, options, &mut watcher, index, &mut last)?;
+            } else if followed.file.is_none() {
+                open_missing(followed, &mut watcher)?;
+            }
+            if followed.file.is_some() {
+                print_new_data(followed, options, index, &mut last)?;
+            }
+        }
+
+        if finished {
+            break;
+        }
+        if files.iter().all(|followed| followed.gone) {
+            eprintln!("no files remaining");
+            break;
+        }
+        watcher.wait(options.interval);
+    }
+
+    Ok(())
+}
+
+
+fn check_name(followed: &mut Followed, options: &Options,
+              watcher: &mut Watcher, index: usize, last: &mut usize)
+    -> Result<()> {
+    // In name mode, notice the file behind the name going away or being
+    // replaced, as happens when logs are rotated.
+    let name = followed.name.clone();
+    match fs::metadata(&name) {
+        Err(err) => {
+            if followed.file.is_some() {
+                // print what the old file got before it went away.
+                print_new_data(followed, options, index, last)?;
+                eprintln!("'{name}' has become inaccessible: {err}");
+                followed.file = None;
+                followed.id = None;
+            }
+            if !options.retry {
+                followed.gone = true;
+            }
+        }
+        Ok(meta) if Some(identity(&meta)) != followed.id => {
+            let replaced = followed.file.is_some();
+            if replaced {
+                print_new_data(followed, options, index, last)?;
+            }
+            match File::open(&name) {
+                Ok(file) => {
+                    if replaced {
+                        eprintln!("'{name}' has been replaced; \
+                                   following new file");
+                    } else {
+                        eprintln!("'{name}' has appeared; \
+                                   following new file");
+                    }
+                    followed.id = Some(identity(&file.metadata()?));
+                    followed.file = Some(file);
+                    followed.pos = 0;
+                    watcher.watch(&name);
+                }
+                Err(err) => {
+                    eprintln!("{name}: {err}");
+                    followed.file = None;
+                    followed.id = None;
+                }
+            }
+        }
+        Ok(_) => {}
+    }
+    Ok(())
+}
+
+
+fn open_missing(followed: &mut Followed, watcher: &mut Watcher) -> Result<()> {
+    // In descriptor mode --retry only covers the first open: a file that
+    // was missing at the start is picked up when it appears, and from then
+    // on followed by descriptor like the rest.
+    if let Ok(file) = File::open(&followed.name) {
+        eprintln!("'{}' has appeared; following new file", followed.name);
+        followed.id = Some(identity(&file.metadata()?));
+        followed.file = Some(file);
+        followed.pos = 0;
+        watcher.watch(&followed.name);
+    }
+    Ok(())
+}
+
+
+fn print_new_data(followed: &mut Followed, options: &Options, index: usize,
+                  last: &mut usize) -> Result<()> {
+    // Print anything added to the file since we last looked. A file that
+    // got shorter has been truncated, so start again from the beginning.
+    let Some(file) = followed.file.as_mut() else {
+        return Ok(());
+    };
+    let len = file.metadata()?.len();
+    if len < followed.pos {
+        eprintln!("{}: file truncated", followed.name);
+        followed.pos = 0;
+    }
+    if len > followed.pos {
+        if options.headers && *last != index {
+            print_header(None, &followed.name, 1);
+        }
+        *last = index;
+        file.seek(SeekFrom::Start(followed.pos))?;
+        io::copy(file, &mut io::stdout().lock())?;
+        followed.pos = file.stream_position()?;
+    }
+    Ok(())
+}
+
+
+/// Waits for something to happen to the followed files: inotify events
+/// where available, otherwise just the polling interval.
+struct Watcher {
+    #[cfg(target_os = "linux")]
+    inotify: Option<std::os::fd::OwnedFd>,
+}
+
+#[cfg(target_os = "linux")]
+impl Watcher {
+    fn new() -> Watcher {
+        use std::os::fd::{FromRawFd, OwnedFd};
+        let flags = libc::IN_NONBLOCK | libc::IN_CLOEXEC;
+        let fd = unsafe { libc::inotify_init1(flags) };
+        Watcher {
+            inotify: (fd >= 0).then(|| unsafe { OwnedFd::from_raw_fd(fd) }),
+        }
+    }
+
+    fn watch(&mut self, name: &str) {
+        // Watch the file itself for writes, and its directory for files
+        // being created, renamed or removed. If a watch can't be added we
+        // still find out about changes on the next poll.
+        use std::ffi::CString;
+        use std::os::fd::AsRawFd;
+        let Some(inotify) = &self.inotify else {
+            return;
+        };
+        let dir = match Path::new(name).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let watches = [
+            (Path::new(name), libc::IN_MODIFY | libc::IN_ATTRIB
+                              | libc::IN_DELETE_SELF | libc::IN_MOVE_SELF),
+            (dir, libc::IN_CREATE | libc::IN_MOVED_TO
+                  | libc::IN_MOVED_FROM | libc::IN_DELETE),
+        ];
+        for (path, mask) in watches {
+            let Ok(path) = CString::new(path.as_os_str().as_encoded_bytes())
+            else {
+                continue;
+            };
+            unsafe {
+                libc::inotify_add_watch(inotify.as_raw_fd(), path.as_ptr(), mask)
+            };
+        }
+    }
+
+    fn wait(&mut self, interval: Duration) {
+        use std::os::fd::AsRawFd;
+        let Some(inotify) = &self.inotify else {
+            std::thread::sleep(interval);
+            return;
+        };
+        let fd = inotify.as_raw_fd();
+        let mut poll = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+        let timeout = interval.as_millis().min(i32::MAX as u128) as i32;
+        unsafe { libc::poll(&mut poll, 1, timeout) };
+        // The events only tell us to look again, so just drain them.
+        let mut buf = [0u8; 4096];
+        while unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) } > 0 {}
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl Watcher {
+    fn new() -> Watcher {
+        Watcher {}
+    }
+
+    fn watch(&mut self, _name: &str) {}
+
+    fn wait(&mut self, interval: Duration) {
+        std::thread::sleep(interval);
+    }
+}
@@ -0,0 +1,333 @@
+use anyhow::Result;
+use clap::{Parser, ValueEnum};
+use follow::{follow, Followed, Options};
+use headr::{open_file, parse_size, print_header};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::time::Duration;
+
+mod follow;
+
+
+#[derive(Debug, Parser)]
+#[command(author, version, about)]
+/// Rust version of tail
+struct Args {
+    /// Input file(s)
+    #[arg(value_name("FILE"),
+          default_value("-")
+    )]
+    files: Vec<String>,
+    /// Number of lines (suffixes such as K or MB allowed); with a leading
+    /// '+', start at line LINES
+    #[arg(value_name("LINES"),
+          short('n'),
+          long,
+          default_value("10"),
+          allow_hyphen_values(true),
+          value_parser(parse_count)
+    )]
+    lines: Count,
+    /// Number of bytes (suffixes such as K or MB allowed); with a leading
+    /// '+', start at byte BYTES
+    #[arg(value_name("BYTES"),
+          short('c'),
+          long, conflicts_with("lines"),
+          allow_hyphen_values(true),
+          value_parser(parse_count)
+    )]
+    bytes: Option<Count>,
+    /// Never print headers giving file names
+    #[arg(short('q'), long, visible_alias("silent"), overrides_with("verbose"))]
+    quiet: bool,
+    /// Always print headers giving file names
+    #[arg(short('v'), long, overrides_with("quiet"))]
+    verbose: bool,
+    /// Output appended data as the file grows
+    #[arg(value_name("HOW"),
+          short('f'),
+          long,
+          value_enum,
+          num_args(0..=1),
+          require_equals(true),
+          default_missing_value("descriptor")
+    )]
+    follow: Option<Follow>,
+    /// Same as --follow=name --retry
+    #[arg(short('F'))]
+    follow_name_retry: bool,
+    /// Keep trying to open a file if it is inaccessible
+    #[arg(long)]
+    retry: bool,
+    /// With -f, seconds to wait between checks for changes
+    #[arg(value_name("N"),
+          short('s'),
+          long,
+          default_value("1.0"),
+          value_parser(parse_interval)
+    )]
+    sleep_interval: Duration,
+    /// With -f, stop after process PID exits
+    #[arg(value_name("PID"), long)]
+    pid: Option<i32>,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Follow {
+    /// Keep reading the file that was opened, even if it is renamed
+    Descriptor,
+    /// Reopen the file name if it is replaced, e.g. by log rotation
+    Name,
+}
+
+
+/// How much of each file to print.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Count {
+    /// The last N lines or bytes.
+    Last(u64),
+    /// Everything from the Nth line or byte on, counting from 1.
+    From(u64),
+}
+
+
+// Size of the blocks read when scanning a file backwards from the end.
+const BLOCK_SIZE: u64 = 64 * 1024;
+
+
+fn parse_count(value: &str) -> Result<Count, String> {
+    // Parse a -n/-c value. A leading '+' counts from the start of the file;
+    // a leading '-' means the same as no sign, as in GNU tail.
+    match value.strip_prefix('+') {
+        Some(num) => parse_size(num).map(Count::From),
+        None => parse_size(value.strip_prefix('-').unwrap_or(value))
+            .map(Count::Last),
+    }
+}
+
+
+fn parse_interval(value: &str) -> Result<Duration, String> {
+    // Parse the -s value: a non-negative number of seconds.
+    value.parse::<f64>()
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .ok_or_else(|| format!("invalid number of seconds: '{value}'"))
+}
+
+
+fn find_line_start(file: &mut File, base: u64, len: u64, num_lines: u64)
+    -> Result<u64> {
+    // Find where the last num_lines lines of a regular file begin by reading
+    // it backwards one block at a time, so only the tail of the file is ever
+    // read no matter how big the file is. Nothing before base is looked at.
+    if num_lines == 0 {
+        return Ok(len);
+    }
+    let mut buf = vec![0; BLOCK_SIZE as usize];
+    let mut end = len;
+    let mut newlines = 0;
+    // a newline as the very last byte ends the last line rather than
+    // starting another one, so it doesn't count.
+    let mut skip_last = true;
+    while end > base {
+        let start = end.saturating_sub(BLOCK_SIZE).max(base);
+        let block = &mut buf[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(block)?;
+        for (i, byte) in block.iter().enumerate().rev() {
+            if skip_last {
+                skip_last = false;
+                if *byte == b'\n' {
+                    continue;
+                }
+            }
+            if *byte == b'\n' {
+                newlines += 1;
+                if newlines == num_lines {
+                    return Ok(start + i as u64 + 1);
+                }
+            }
+        }
+        end = start;
+    }
+    // fewer lines than asked for, so the whole file.
+    Ok(base)
+}
+
+
+fn tail_seekable(file: &mut File, count: Count, bytes: bool) -> Result<()> {
+    // A regular file: work out where the output starts, seek there and
+    // copy the rest. A file given as stdin may already be part way through,
+    // in which case only the part from there on counts.
+    let base = file.stream_position()?;
+    let len = file.metadata()?.len().max(base);
+    let start = match (count, bytes) {
+        (Count::Last(num_bytes), true) => len.saturating_sub(num_bytes).max(base),
+        (Count::From(num_bytes), true) => {
+            base.saturating_add(num_bytes.saturating_sub(1)).min(len)
+        }
+        (Count::Last(num_lines), false) => {
+            find_line_start(file, base, len, num_lines)?
+        }
+        (Count::From(_), false) => {
+            return tail_stream(BufReader::new(file), count, bytes);
+        }
+    };
+    file.seek(SeekFrom::Start(start))?;
+    io::copy(file, &mut io::stdout().lock())?;
+    Ok(())
+}
+
+
+fn tail_stream(mut file: impl BufRead, count: Count, bytes: bool) -> Result<()> {
+    // Anything that can't seek (pipes, terminals) has to be read from the
+    // start, holding back just enough to print at the end.
+    let mut out = io::stdout().lock();
+    match (count, bytes) {
+        (Count::From(num_bytes), true) => {
+            let skip = num_bytes.saturating_sub(1);
+            io::copy(&mut file.by_ref().take(skip), &mut io::sink())?;
+            io::copy(&mut file, &mut out)?;
+        }
+        (Count::From(num_lines), false) => {
+            let mut buf = Vec::new();
+            for _ in 1..num_lines {
+                if file.read_until(b'\n', &mut buf)? == 0 {
+                    break;
+                }
+                buf.clear();
+            }
+            io::copy(&mut file, &mut out)?;
+        }
+        (Count::Last(num_bytes), true) => {
+            // ring buffer of the last num_bytes bytes.
+            let mut held: VecDeque<u8> = VecDeque::new();
+            loop {
+                let chunk = file.fill_buf()?;
+                if chunk.is_empty() {
+                    break;
+                }
+                let len = chunk.len();
+                held.extend(chunk);
+                file.consume(len);
+                if held.len() as u64 > num_bytes {
+                    held.drain(..held.len() - num_bytes as usize);
+                }
+            }
+            let (front, back) = held.as_slices();
+            out.write_all(front)?;
+            out.write_all(back)?;
+        }
+        (Count::Last(num_lines), false) => {
+            // ring buffer of the last num_lines lines.
+            let mut held: VecDeque<Vec<u8>> = VecDeque::new();
+            let mut buf = Vec::new();
+            while file.read_until(b'\n', &mut buf)? > 0 {
+                if num_lines > 0 {
+                    if held.len() as u64 == num_lines {
+                        held.pop_front();
+                    }
+                    held.push_back(std::mem::take(&mut buf));
+                } else {
+                    buf.clear();
+                }
+            }
+            for line in held {
+                out.write_all(&line)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+
+fn run(args: Args) -> Result<()> {
+    let (count, bytes) = match args.bytes {
+        Some(count) => (count, true),
+        None => (args.lines, false),
+    };
+    let follow_mode = if args.follow_name_retry {
+        Some(Follow::Name)
+    } else {
+        args.follow
+    };
+    let retry = args.retry || args.follow_name_retry;
+    // as GNU tail does, say when --retry won't do all it might seem to.
+    match follow_mode {
+        None if retry => {
+            eprintln!("warning: --retry ignored; --retry is useful only \
+                       when following");
+        }
+        Some(Follow::Descriptor) if retry => {
+            eprintln!("warning: --retry only effective for the initial open");
+        }
+        _ => {}
+    }
+    // Like GNU tail, asking for nothing means not even opening the files,
+    // unless we are going to follow them.
+    if count == Count::Last(0) && follow_mode.is_none() {
+        return Ok(());
+    }
+
+    let headers = args.verbose || (args.files.len() > 1 && !args.quiet);
+    let mut followed = Vec::new();
+    // which of the followed files was printed last, so following knows
+    // when it needs a header; usize::MAX for none of them.
+    let mut last = usize::MAX;
+    for (file_count, filename) in args.files.iter().enumerate() {
+        match open_file(filename) {
+            // If there is a problem opening the file, note it and move on.
+            Err(err) => {
+                eprintln!("{filename}: {err}");
+                if follow_mode.is_some() && retry {
+                    followed.push(Followed::missing(filename));
+                }
+            }
+            Ok(mut current_file) => {
+                if headers {
+                    print_header(None, filename, file_count);
+                }
+                if current_file.metadata()?.is_file() {
+                    tail_seekable(&mut current_file, count, bytes)?;
+                    // only regular files can grow; a pipe is done once
+                    // it has been read to the end.
+                    if follow_mode.is_some() {
+                        let pos = current_file.stream_position()?;
+                        last = followed.len();
+                        followed.push(Followed::open(filename, current_file, pos)?);
+                    }
+                } else {
+                    tail_stream(BufReader::new(current_file), count, bytes)?;
+                    last = usize::MAX;
+                }
+            },
+        }
+    }
+
+    if follow_mode.is_some() {
+        // nothing that can grow, e.g. only a pipe on stdin.
+        if followed.is_empty() {
+            return Ok(());
+        }
+        let options = Options {
+            by_name: follow_mode == Some(Follow::Name),
+            retry,
+            interval: args.sleep_interval,
+            pid: args.pid,
+            headers,
+        };
+        follow(followed, &options, last)?;
+    }
+
+    Ok(())
+}
+
+
+fn main() {
+    if let Err(e) = run(Args::parse()) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
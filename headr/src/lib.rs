@@ -0,0 +1,173 @@
+//! Pieces shared by the headr and tailr binaries.
+
+use std::fs::{self, File, Metadata};
+use std::io;
+use std::num::{IntErrorKind, ParseIntError};
+use std::os::fd::AsFd;
+
+
+/// Parse a number with an optional GNU-style multiplier suffix:
+/// b (512), K/KiB (1024), kB (1000), and likewise M, G, T, P and E.
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (digits, suffix) = value.split_at(split);
+    let multiplier: u64 = match suffix {
+        "" => 1,
+        "b" => 512,
+        _ => {
+            let mut chars = suffix.chars();
+            let power = match chars.next() {
+                Some('k' | 'K') => 1,
+                Some('m' | 'M') => 2,
+                Some('G') => 3,
+                Some('T') => 4,
+                Some('P') => 5,
+                Some('E') => 6,
+                _ => 0,
+            };
+            let base: u64 = match chars.as_str() {
+                "" | "iB" => 1024,
+                "B" => 1000,
+                _ => 0,
+            };
+            if power == 0 || base == 0 {
+                // Not a suffix we know, so report it like any other
+                // malformed number.
+                return value.parse().map_err(|e: ParseIntError| e.to_string());
+            }
+            base.pow(power)
+        }
+    };
+    let num: u64 = digits.parse().map_err(|e: ParseIntError| {
+        match e.kind() {
+            IntErrorKind::PosOverflow => too_large(),
+            _ => e.to_string(),
+        }
+    })?;
+    num.checked_mul(multiplier).ok_or_else(too_large)
+}
+
+
+fn too_large() -> String {
+    format!("number too large, the maximum is {}", u64::MAX)
+}
+
+
+/// A parsed --header-format template.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderFormat(Vec<HeaderPart>);
+
+#[derive(Debug, Clone, PartialEq)]
+enum HeaderPart {
+    Text(String),
+    /// The file name as given on the command line.
+    Name,
+    /// The file size in bytes.
+    Size,
+    /// The modification time as an RFC 3339 UTC timestamp.
+    Mtime,
+    /// The 1-based position of the file on the command line.
+    Index,
+}
+
+
+/// Split a --header-format template into literal text and placeholders.
+/// '{{' and '}}' stand for literal braces.
+pub fn parse_header_format(value: &str) -> Result<HeaderFormat, String> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let rest = chars.as_str();
+                let end = rest.find('}')
+                    .ok_or_else(|| "unclosed '{' in header format".to_string())?;
+                let part = match &rest[..end] {
+                    "name" => HeaderPart::Name,
+                    "size" => HeaderPart::Size,
+                    "mtime" => HeaderPart::Mtime,
+                    "index" => HeaderPart::Index,
+                    other => return Err(format!("unknown placeholder '{{{other}}}'")),
+                };
+                if !text.is_empty() {
+                    parts.push(HeaderPart::Text(std::mem::take(&mut text)));
+                }
+                parts.push(part);
+                chars = rest[end + 1..].chars();
+            }
+            '}' => return Err("unmatched '}' in header format".to_string()),
+            _ => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        parts.push(HeaderPart::Text(text));
+    }
+    Ok(HeaderFormat(parts))
+}
+
+
+/// Metadata for a file name, or for whatever stdin is connected to.
+pub fn metadata(filename: &str) -> io::Result<Metadata> {
+    match filename {
+        "-" => stdin_file()?.metadata(),
+        _ => fs::metadata(filename),
+    }
+}
+
+
+/// Fill in a header template. Size and time are left blank when they
+/// can't be determined.
+pub fn format_header(format: &HeaderFormat, filename: &str, index: usize) -> String {
+    let info = metadata(filename).ok();
+    format.0.iter().map(|part| match part {
+        HeaderPart::Text(text) => text.clone(),
+        HeaderPart::Name => filename.to_string(),
+        HeaderPart::Size => info.as_ref()
+            .map(|m| m.len().to_string())
+            .unwrap_or_default(),
+        HeaderPart::Mtime => info.as_ref()
+            .and_then(|m| m.modified().ok())
+            .map(|t| humantime::format_rfc3339_seconds(t).to_string())
+            .unwrap_or_default(),
+        HeaderPart::Index => (index + 1).to_string(),
+    }).collect()
+}
+
+
+/// A File on a duplicate of the stdin descriptor. It shares the file
+/// offset with stdin but, unlike io::stdin(), has no buffer of its own
+/// hidden away in std, so whatever we don't consume stays in the input.
+pub fn stdin_file() -> io::Result<File> {
+    Ok(File::from(io::stdin().as_fd().try_clone_to_owned()?))
+}
+
+
+/// Open stdin or a file, depending on the file name.
+pub fn open_file(filename: &str) -> io::Result<File> {
+    match filename {
+        "-" => stdin_file(),
+        _ => File::open(filename),
+    }
+}
+
+
+/// Print the header that goes before a file's contents: the template if
+/// one was given, otherwise `==> name <==`, preceded by a blank line for
+/// every file but the first.
+pub fn print_header(format: Option<&HeaderFormat>, filename: &str, index: usize) {
+    match format {
+        // a custom header goes on its own line exactly as formatted, so
+        // it's easy to match in scripts.
+        Some(format) => println!("{}", format_header(format, filename, index)),
+        None => println!("{}==> {filename} <==", if index > 0 {"\n"} else {""}),
+    }
+}
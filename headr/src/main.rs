@@ -1,11 +1,23 @@
-use anyhow::Result;
-use clap::Parser;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use anyhow::{anyhow, bail, Result};
+use clap::{ArgGroup, Parser, ValueEnum};
+use glob::Pattern;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use regex::bytes::Regex;
+use std::collections::VecDeque;
+use headr::{open_file, parse_header_format, parse_size, print_header,
+            stdin_file, HeaderFormat};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use walkdir::WalkDir;
 
 
 #[derive(Debug, Parser)]
 #[command(author, version, about)]
+#[command(group(ArgGroup::new("pattern").args(["until", "through"])))]
 /// Rust version of head
 struct Args {
     /// Input file(s)
@@ -13,102 +25,1054 @@ struct Args {
           default_value("-")
     )]
     files: Vec<String>,
-    /// Number of lines
+    /// Number of lines (suffixes such as K or MB allowed); with a leading
+    /// '-', all but the last LINES lines
     #[arg(value_name("LINES"),
           short('n'),
           long,
           default_value("10"),
-          value_parser(clap::value_parser!(u64).range(1..))
+          allow_hyphen_values(true),
+          value_parser(parse_count)
     )]
-    lines: u64,
-    /// Number of bytes
+    lines: Count,
+    /// Number of bytes (suffixes such as K or MB allowed); with a leading
+    /// '-', all but the last BYTES bytes
     #[arg(value_name("BYTES"),
           short('c'),
           long, conflicts_with("lines"),
+          allow_hyphen_values(true),
+          value_parser(parse_count)
+    )]
+    bytes: Option<Count>,
+    /// Number of characters (Unicode scalar values)
+    #[arg(value_name("CHARS"),
+          long, conflicts_with_all(["lines", "bytes"]),
+          value_parser(parse_size)
+    )]
+    chars: Option<u64>,
+    /// Number of user-perceived characters (extended grapheme clusters)
+    #[arg(value_name("GRAPHEMES"),
+          long, conflicts_with_all(["lines", "bytes", "chars"]),
+          value_parser(parse_size)
+    )]
+    graphemes: Option<u64>,
+    /// Stop before the first line matching REGEX
+    #[arg(value_name("REGEX"),
+          long,
+          conflicts_with_all(["lines", "bytes", "chars", "graphemes"]),
+          value_parser(parse_regex)
+    )]
+    until: Option<Regex>,
+    /// Stop after the first line matching REGEX
+    #[arg(value_name("REGEX"),
+          long,
+          conflicts_with_all(["lines", "bytes", "chars", "graphemes"]),
+          value_parser(parse_regex)
+    )]
+    through: Option<Regex>,
+    /// Stop at the Nth matching line instead of the first
+    #[arg(value_name("N"),
+          long,
+          default_value("1"),
+          requires("pattern"),
+          value_parser(clap::value_parser!(u64).range(1..))
+    )]
+    match_count: u64,
+    /// Print only these lines, e.g. 100-120,500- (numbered from 1)
+    #[arg(value_name("RANGES"),
+          long,
+          conflicts_with_all(["lines", "bytes", "chars", "graphemes",
+                              "pattern"]),
+          value_parser(parse_ranges)
+    )]
+    range: Option<Ranges>,
+    /// Print only these bytes, e.g. 1-512,4K- (numbered from 1)
+    #[arg(value_name("RANGES"),
+          long,
+          conflicts_with_all(["lines", "bytes", "chars", "graphemes",
+                              "pattern", "range"]),
+          value_parser(parse_ranges)
+    )]
+    bytes_range: Option<Ranges>,
+    /// Print N lines chosen at random from each file
+    #[arg(value_name("N"),
+          long,
+          conflicts_with_all(["lines", "bytes", "chars", "graphemes",
+                              "pattern", "range", "bytes_range"]),
+          value_parser(parse_size)
+    )]
+    sample: Option<u64>,
+    /// Seed for --sample, to get the same lines every time
+    #[arg(value_name("SEED"), long, requires("sample"))]
+    seed: Option<u64>,
+    /// Print the sampled lines in the order they appear in the file
+    #[arg(long, requires("sample"))]
+    keep_order: bool,
+    /// Count CSV records rather than lines, always keeping the header row
+    #[arg(long,
+          conflicts_with_all(["bytes", "chars", "graphemes", "pattern",
+                              "range", "bytes_range", "sample"])
+    )]
+    csv: bool,
+    /// Count JSON Lines records, checking that each one printed is valid;
+    /// blank lines aren't records, so they are rejected too
+    #[arg(long,
+          conflicts_with_all(["bytes", "chars", "graphemes", "pattern",
+                              "range", "bytes_range", "sample", "csv"])
+    )]
+    jsonl: bool,
+    /// What to do with binary files: print them as they are, skip them, or
+    /// show a hex preview
+    #[arg(value_name("TYPE"),
+          long,
+          value_enum,
+          default_value("text")
+    )]
+    binary_files: BinaryFiles,
+    /// Print the head of every file under directories given as FILE
+    #[arg(short('r'), long)]
+    recursive: bool,
+    /// With -r, only print files whose names match GLOB
+    #[arg(value_name("GLOB"), long, requires("recursive"),
+          value_parser(parse_glob)
+    )]
+    include: Vec<Pattern>,
+    /// With -r, skip files and directories whose names match GLOB
+    #[arg(value_name("GLOB"), long, requires("recursive"),
+          value_parser(parse_glob)
+    )]
+    exclude: Vec<Pattern>,
+    /// With -r, descend at most DEPTH directories below each FILE
+    #[arg(value_name("DEPTH"), long, requires("recursive"))]
+    max_depth: Option<usize>,
+    /// With -r, also print hidden files and look in hidden directories
+    #[arg(long, requires("recursive"))]
+    hidden: bool,
+    /// Cut lines longer than COLS display columns, noting how many bytes
+    /// were left out
+    #[arg(value_name("COLS"),
+          long,
+          conflicts_with_all(["bytes", "chars", "graphemes", "bytes_range"]),
           value_parser(clap::value_parser!(u64).range(1..))
     )]
-    bytes: Option<u64>,
+    max_line_width: Option<u64>,
+    /// Lines end with NUL rather than newline
+    #[arg(short('z'), long, conflicts_with_all(["csv", "jsonl"]))]
+    zero_terminated: bool,
+    /// After each file, report how many of its lines and bytes were shown,
+    /// on stderr or inline after the output
+    #[arg(value_name("WHERE"),
+          long,
+          value_enum,
+          num_args(0..=1),
+          require_equals(true),
+          default_missing_value("stderr")
+    )]
+    stats: Option<Stats>,
+    /// Never print headers giving file names
+    #[arg(short('q'), long, visible_alias("silent"), overrides_with("verbose"))]
+    quiet: bool,
+    /// Always print headers giving file names
+    #[arg(short('v'), long, overrides_with("quiet"))]
+    verbose: bool,
+    /// Header template; placeholders {name}, {size}, {mtime} and {index}
+    #[arg(value_name("FORMAT"),
+          long,
+          value_parser(parse_header_format)
+    )]
+    header_format: Option<HeaderFormat>,
+}
+
+
+/// How much of each file to print.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Count {
+    /// The first N lines or bytes.
+    First(u64),
+    /// Everything except the last N lines or bytes.
+    AllButLast(u64),
+}
+
+
+/// A 1-based, inclusive run of lines or bytes; no end means through to the
+/// end of the input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Span {
+    start: u64,
+    end: Option<u64>,
+}
+
+impl Span {
+    fn contains(&self, num: u64) -> bool {
+        num >= self.start && self.end.is_none_or(|end| num <= end)
+    }
+}
+
+
+/// The spans given to --range or --bytes-range, sorted and with any
+/// overlapping or adjacent ones merged, so they can be printed in one pass.
+#[derive(Debug, Clone, PartialEq)]
+struct Ranges(Vec<Span>);
+
+
+fn parse_ranges(value: &str) -> Result<Ranges, String> {
+    // Parse a comma-separated list of N, N-M, N- or -M. Numbers may have
+    // the same suffixes as -n and -c.
+    let mut spans = Vec::new();
+    for part in value.split(',') {
+        let number = |num: &str| -> Result<u64, String> {
+            match parse_size(num)? {
+                0 => Err("lines and bytes are numbered from 1".to_string()),
+                num => Ok(num),
+            }
+        };
+        let span = match part.split_once('-') {
+            None if part.is_empty() => return Err("empty range".to_string()),
+            None => Span { start: number(part)?, end: Some(number(part)?) },
+            Some(("", "")) => return Err(format!("invalid range '{part}'")),
+            Some(("", end)) => Span { start: 1, end: Some(number(end)?) },
+            Some((start, "")) => Span { start: number(start)?, end: None },
+            Some((start, end)) => {
+                let span = Span { start: number(start)?, end: Some(number(end)?) };
+                if span.end < Some(span.start) {
+                    return Err(format!("invalid decreasing range '{part}'"));
+                }
+                span
+            }
+        };
+        spans.push(span);
+    }
+
+    spans.sort_by_key(|span| span.start);
+    let mut merged: Vec<Span> = Vec::new();
+    for span in spans {
+        match merged.last_mut() {
+            Some(last) if last.end
+                .is_none_or(|end| span.start <= end.saturating_add(1)) => {
+                last.end = match (last.end, span.end) {
+                    (Some(a), Some(b)) => Some(a.max(b)),
+                    _ => None,
+                };
+            }
+            _ => merged.push(span),
+        }
+    }
+    Ok(Ranges(merged))
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum BinaryFiles {
+    Text,
+    Skip,
+    Hex,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Stats {
+    Stderr,
+    Inline,
+}
+
+
+/// What -n counts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Records {
+    Lines,
+    /// CSV records, which can run over several lines when a quoted field
+    /// contains a newline. The first one is the header row.
+    Csv,
+    /// One JSON value per line.
+    Jsonl,
+}
+
+
+fn parse_count(value: &str) -> Result<Count, String> {
+    // Parse a -n/-c value. A leading '-' selects everything but the last N,
+    // as in GNU head; zero is allowed either way.
+    match value.strip_prefix('-') {
+        Some(num) => parse_size(num).map(Count::AllButLast),
+        None => parse_size(value).map(Count::First),
+    }
+}
+
+
+fn parse_glob(value: &str) -> Result<Pattern, String> {
+    Pattern::new(value).map_err(|e| e.to_string())
+}
+
+
+fn parse_regex(value: &str) -> Result<Regex, String> {
+    Regex::new(value).map_err(|e| e.to_string())
+}
+
+
+/// How lines are read and written in line mode.
+#[derive(Debug, Clone, Copy)]
+struct LineMode {
+    /// What ends a line: newline, or NUL with -z.
+    delimiter: u8,
+    /// Display columns to cut lines down to (--max-line-width).
+    max_width: Option<u64>,
+}
+
+impl LineMode {
+    fn strip<'a>(&self, line: &'a [u8]) -> &'a [u8] {
+        // line without its ending, which for newlines may be "\r\n".
+        match line.strip_suffix(&[self.delimiter]) {
+            Some(body) if self.delimiter == b'\n' => {
+                body.strip_suffix(b"\r").unwrap_or(body)
+            }
+            Some(body) => body,
+            None => line,
+        }
+    }
 }
 
 
-fn open(filename: &str) -> Result<Box<dyn BufRead>> {
-    // Open stdin or a file for reading, depending on filename passed.
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+/// Output that can be given text of our own, like the --max-line-width
+/// marker, apart from what comes from the input, so that --stats doesn't
+/// count it as shown.
+trait Output: Write {
+    fn write_note(&mut self, note: &[u8]) -> io::Result<()>;
+}
+
+impl<W: Write> Output for Tally<W> {
+    fn write_note(&mut self, note: &[u8]) -> io::Result<()> {
+        self.inner.write_all(note)?;
+        // the output can still end with a note.
+        self.last = note.last().copied().or(self.last);
+        Ok(())
     }
 }
 
 
-fn run(args: Args) -> Result<()> {
-    for (file_count, filename) in args.files.iter().enumerate() {
-        match open(&filename) {
+/// Line-mode output, with lines cut down to --max-line-width columns when
+/// that is given.
+struct LineOut<'a, W: Output> {
+    out: &'a mut W,
+    mode: LineMode,
+}
+
+impl<'a, W: Output> LineOut<'a, W> {
+    fn new(out: &'a mut W, mode: LineMode) -> LineOut<'a, W> {
+        LineOut { out, mode }
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        // Cut what comes before the line ending and say how many bytes went
+        // missing; the line ending itself is always kept.
+        let body = self.mode.strip(line);
+        match self.mode.max_width.and_then(|width| fit_width(body, width)) {
+            None => self.out.write_all(line),
+            Some(keep) => {
+                self.out.write_all(&body[..keep])?;
+                let note = format!("…[{} bytes omitted]", body.len() - keep);
+                self.out.write_note(note.as_bytes())?;
+                self.out.write_all(&line[body.len()..])
+            }
+        }
+    }
+}
+
+
+fn fit_width(line: &[u8], max_width: u64) -> Option<usize> {
+    // Byte length of the longest start of line that fits in max_width
+    // display columns, or None if all of it does. Grapheme clusters are
+    // never split; a tab runs to the next 8-column stop, other control
+    // characters take no columns, and a sequence that isn't valid UTF-8
+    // takes one, as U+FFFD would.
+    let mut width = 0;
+    let mut offset = 0;
+    for chunk in line.utf8_chunks() {
+        for (i, grapheme) in chunk.valid().grapheme_indices(true) {
+            if grapheme == "\t" {
+                width = (width / 8 + 1) * 8;
+            } else if !grapheme.starts_with(char::is_control) {
+                width += grapheme.width() as u64;
+            }
+            if width > max_width {
+                return Some(offset + i);
+            }
+        }
+        offset += chunk.valid().len();
+        if !chunk.invalid().is_empty() {
+            width += 1;
+            if width > max_width {
+                return Some(offset);
+            }
+            offset += chunk.invalid().len();
+        }
+    }
+    None
+}
+
+
+fn read_record(file: &mut impl BufRead, buf: &mut Vec<u8>, records: Records,
+               delimiter: u8) -> io::Result<usize> {
+    // Append the next record to buf and return its length, as read_until()
+    // does for a line.
+    let mut len = file.read_until(delimiter, buf)?;
+    if records == Records::Csv {
+        // A newline inside quotes doesn't end the record. An escaped quote
+        // is written as two, so an odd number of quotes so far means we are
+        // still in a quoted field.
+        while len > 0 && buf.iter().filter(|&&b| b == b'"').count() % 2 == 1 {
+            let more = file.read_until(b'\n', buf)?;
+            if more == 0 {
+                break;
+            }
+            len += more;
+        }
+    }
+    Ok(len)
+}
+
+
+fn check_record(record: &[u8], num: u64, records: Records) -> Result<()> {
+    // Make sure a JSON Lines record parses before it is printed.
+    if records == Records::Jsonl {
+        if record.trim_ascii().is_empty() {
+            bail!("record {num} is blank");
+        }
+        serde_json::from_slice::<serde_json::Value>(record)
+            .map_err(|e| anyhow!("record {num} is not valid JSON: {e}"))?;
+    }
+    Ok(())
+}
+
+
+fn print_lines(mut file: impl BufRead, out: &mut impl Output, count: Count,
+               records: Records, mode: LineMode) -> Result<u64> {
+    // Print the selected lines, or records, and return how many bytes of
+    // input they took up.
+    let mut out = LineOut::new(out, mode);
+    let mut printed = 0;
+    let mut num = 0;
+    let mut buf = Vec::new();
+    if records == Records::Csv {
+        // the header row is printed whatever the count.
+        read_record(&mut file, &mut buf, records, mode.delimiter)?;
+        out.write_line(&buf)?;
+        printed += buf.len() as u64;
+        buf.clear();
+    }
+    match count {
+        Count::First(num_lines) => {
+            // try to read the desired number of lines.
+            for _ in 0..num_lines {
+                // using read_until() instead of lines() to preserve
+                // line endings and any bytes that aren't valid UTF-8.
+                // if we reach the end of the file before reading the
+                // requested number of lines, break out of the loop,
+                // we're done.
+                if read_record(&mut file, &mut buf, records, mode.delimiter)? == 0 {
+                    break;
+                }
+                num += 1;
+                check_record(&buf, num, records)?;
+                out.write_line(&buf)?;
+                printed += buf.len() as u64;
+                buf.clear();
+            }
+        }
+        Count::AllButLast(num_lines) => {
+            // Hold back the most recent num_lines lines in a ring buffer; a
+            // line is only printed once enough lines have been read after
+            // it to know it isn't one of the last ones.
+            let mut held: VecDeque<Vec<u8>> = VecDeque::new();
+            while read_record(&mut file, &mut buf, records, mode.delimiter)? > 0 {
+                held.push_back(std::mem::take(&mut buf));
+                if held.len() as u64 > num_lines {
+                    if let Some(line) = held.pop_front() {
+                        num += 1;
+                        check_record(&line, num, records)?;
+                        out.write_line(&line)?;
+                        printed += line.len() as u64;
+                    }
+                }
+            }
+        }
+    }
+    Ok(printed)
+}
+
+
+fn print_bytes(mut file: impl BufRead, out: &mut impl Write, count: Count)
+    -> Result<u64> {
+    // Print the selected bytes and return how many were printed.
+    let mut printed = 0;
+    match count {
+        Count::First(num_bytes) => {
+            // Copy through io::copy's fixed-size buffer rather than
+            // allocating num_bytes up front, so memory use doesn't depend on
+            // the count. take() stops after num_bytes, and io::copy keeps
+            // reading past short reads until then or the end of the input.
+            printed = io::copy(&mut file.take(num_bytes), out)?;
+        }
+        Count::AllButLast(num_bytes) => {
+            // Same idea as for lines: keep the last num_bytes bytes seen
+            // and write out whatever gets pushed past them.
+            let mut held: VecDeque<u8> = VecDeque::new();
+            loop {
+                let chunk = file.fill_buf()?;
+                if chunk.is_empty() {
+                    break;
+                }
+                let len = chunk.len();
+                held.extend(chunk);
+                file.consume(len);
+                if held.len() as u64 > num_bytes {
+                    let excess = held.len() - num_bytes as usize;
+                    let (front, back) = held.as_slices();
+                    if excess <= front.len() {
+                        out.write_all(&front[..excess])?;
+                    } else {
+                        out.write_all(front)?;
+                        out.write_all(&back[..excess - front.len()])?;
+                    }
+                    held.drain(..excess);
+                    printed += excess as u64;
+                }
+            }
+        }
+    }
+    Ok(printed)
+}
+
+
+fn print_chars(mut file: impl BufRead, out: &mut impl Write, num_chars: u64,
+               graphemes: bool) -> Result<u64> {
+    // Print the first num_chars characters, or grapheme clusters, and
+    // return how many bytes that was. Neither can span a newline, so the
+    // input is handled a line at a time.
+    let mut printed = 0;
+    let mut left = num_chars;
+    let mut buf = Vec::new();
+    while left > 0 && file.read_until(b'\n', &mut buf)? > 0 {
+        let len = chars_len(&buf, &mut left, graphemes);
+        out.write_all(&buf[..len])?;
+        printed += len as u64;
+        buf.clear();
+    }
+    Ok(printed)
+}
+
+
+fn chars_len(line: &[u8], left: &mut u64, graphemes: bool) -> usize {
+    // Byte length of the first `left` characters (or grapheme clusters) of
+    // line, or of the whole line if it has fewer; `left` is reduced by the
+    // number taken. Each sequence that isn't valid UTF-8 counts as a single
+    // character, the same as it would as U+FFFD in a lossy conversion.
+    let mut offset = 0;
+    let mut len = 0;
+    for chunk in line.utf8_chunks() {
+        let valid = chunk.valid();
+        let chunk_len = valid.len() + chunk.invalid().len();
+        // where each character in the chunk ends, relative to its start.
+        let mut ends: Vec<usize> = if graphemes {
+            valid.grapheme_indices(true).map(|(i, g)| i + g.len()).collect()
+        } else {
+            valid.char_indices().map(|(i, c)| i + c.len_utf8()).collect()
+        };
+        if !chunk.invalid().is_empty() {
+            ends.push(chunk_len);
+        }
+        for end in ends {
+            if *left == 0 {
+                return len;
+            }
+            *left -= 1;
+            len = offset + end;
+        }
+        offset += chunk_len;
+    }
+    len
+}
+
+
+fn print_until(mut file: impl BufRead, out: &mut impl Output, regex: &Regex,
+               num_matches: u64, through: bool, mode: LineMode)
+    -> Result<u64> {
+    // Print lines up to the num_matches-th line matching regex, including
+    // that line only if through is set, and return how many bytes that was.
+    // Nothing past the matching line is read, so this works on input that
+    // never ends.
+    let mut out = LineOut::new(out, mode);
+    let mut printed = 0;
+    let mut matches = 0;
+    let mut buf = Vec::new();
+    while file.read_until(mode.delimiter, &mut buf)? > 0 {
+        // match without the line ending so that '$' works as expected.
+        if regex.is_match(mode.strip(&buf)) {
+            matches += 1;
+            if matches == num_matches {
+                if through {
+                    out.write_line(&buf)?;
+                    printed += buf.len() as u64;
+                }
+                break;
+            }
+        }
+        out.write_line(&buf)?;
+        printed += buf.len() as u64;
+        buf.clear();
+    }
+    Ok(printed)
+}
+
+
+fn print_line_ranges(mut file: impl BufRead, out: &mut impl Output,
+                     ranges: &Ranges, mode: LineMode) -> Result<u64> {
+    // Print the lines that fall in the ranges and return how many bytes of
+    // input were read. Reading stops after the end of the last range.
+    let mut out = LineOut::new(out, mode);
+    let mut consumed = 0;
+    let mut buf = Vec::new();
+    let mut spans = ranges.0.iter().peekable();
+    let mut line_num = 0;
+    while let Some(span) = spans.peek() {
+        if span.end.is_some_and(|end| line_num >= end) {
+            spans.next();
+            continue;
+        }
+        if file.read_until(mode.delimiter, &mut buf)? == 0 {
+            break;
+        }
+        line_num += 1;
+        consumed += buf.len() as u64;
+        if span.contains(line_num) {
+            out.write_line(&buf)?;
+        }
+        buf.clear();
+    }
+    Ok(consumed)
+}
+
+
+fn print_byte_ranges(file: &mut BufReader<Tally<File>>, out: &mut impl Write,
+                     ranges: &Ranges) -> Result<u64> {
+    // Print the bytes that fall in the ranges and return how far into the
+    // input that went. Regular files are seeked straight to each range;
+    // anything else has to be read through.
+    let meta = file.get_ref().inner.metadata()?;
+    if meta.is_file() {
+        // stdin may already be part way into the file, and the binary file
+        // check may have read some of it into the buffer.
+        let base = file.stream_position()?;
+        let mut pos = 0;
+        for span in &ranges.0 {
+            // a range that starts past the end has nothing to print, and
+            // its start may be too far out to seek to at all.
+            let Some(offset) = base.checked_add(span.start - 1)
+                .filter(|&offset| offset < meta.len()) else {
+                break;
+            };
+            file.seek(SeekFrom::Start(offset))?;
+            let len = span.end.map_or(u64::MAX, |end| end - span.start + 1);
+            let copied = io::copy(&mut file.by_ref().take(len), out)?;
+            if copied == 0 {
+                break;
+            }
+            pos = span.start - 1 + copied;
+        }
+        return Ok(pos);
+    }
+
+    let mut pos = 0;
+    for span in &ranges.0 {
+        let skip = span.start - 1 - pos;
+        let skipped = io::copy(&mut file.by_ref().take(skip), &mut io::sink())?;
+        pos += skipped;
+        if skipped < skip {
+            break;
+        }
+        let len = span.end.map_or(u64::MAX, |end| end - span.start + 1);
+        pos += io::copy(&mut file.by_ref().take(len), out)?;
+    }
+    Ok(pos)
+}
+
+
+fn print_sample(mut file: impl BufRead, out: &mut impl Output, num_lines: u64,
+                keep_order: bool, rng: &mut StdRng, mode: LineMode)
+    -> Result<u64> {
+    // Print num_lines lines picked uniformly at random, in one pass and
+    // holding no more than num_lines lines, and return how many bytes of
+    // input were read. This is reservoir sampling: the first num_lines
+    // lines fill the reservoir, then line i (counting from 0) replaces a
+    // random one of them with probability num_lines / (i + 1).
+    let mut out = LineOut::new(out, mode);
+    let mut consumed = 0;
+    let mut reservoir: Vec<(u64, Vec<u8>)> = Vec::new();
+    let mut buf = Vec::new();
+    let mut line_num = 0;
+    while file.read_until(mode.delimiter, &mut buf)? > 0 {
+        consumed += buf.len() as u64;
+        if line_num < num_lines {
+            reservoir.push((line_num, std::mem::take(&mut buf)));
+        } else {
+            let slot = rng.gen_range(0..=line_num);
+            if slot < num_lines {
+                reservoir[slot as usize] = (line_num, std::mem::take(&mut buf));
+            }
+        }
+        buf.clear();
+        line_num += 1;
+    }
+
+    if keep_order {
+        reservoir.sort_by_key(|(line_num, _)| *line_num);
+    } else {
+        // the reservoir still holds its first lines in file order.
+        reservoir.shuffle(rng);
+    }
+    for (_, line) in reservoir {
+        out.write_line(&line)?;
+    }
+    Ok(consumed)
+}
+
+
+fn is_binary(block: &[u8]) -> bool {
+    // Guess from the start of a file whether it is binary: it is if there
+    // is a NUL byte, or if more than 30% of it is control characters or
+    // isn't valid UTF-8. A character cut off at the end of the block counts
+    // as invalid, but that is at most three bytes.
+    if block.contains(&0) {
+        return true;
+    }
+    let is_control = |b: &u8| {
+        (*b < 0x20 && !b"\t\n\r\x0b\x0c\x1b".contains(b)) || *b == 0x7f
+    };
+    let control = block.iter().filter(|b| is_control(b)).count();
+    let invalid: usize = block.utf8_chunks().map(|c| c.invalid().len()).sum();
+    (control + invalid) * 10 > block.len() * 3
+}
+
+
+/// How much of a binary pipe or device --binary-files=skip reads to find
+/// its size.
+const BINARY_SIZE_LIMIT: u64 = 1024 * 1024;
+
+
+fn print_binary(file: &mut BufReader<Tally<File>>, out: &mut impl Write,
+                binary_files: BinaryFiles) -> Result<u64> {
+    // Print a note or a hex preview in place of a binary file's contents,
+    // and return how many bytes of it were shown.
+    if binary_files == BinaryFiles::Hex {
+        // At most four lines in the style of `hexdump -C`.
+        let mut preview = Vec::new();
+        file.by_ref().take(64).read_to_end(&mut preview)?;
+        for (row, chunk) in preview.chunks(16).enumerate() {
+            let mut hex = String::new();
+            for (i, byte) in chunk.iter().enumerate() {
+                hex.push_str(if i == 8 { "  " } else { " " });
+                hex.push_str(&format!("{byte:02x}"));
+            }
+            let ascii: String = chunk.iter()
+                .map(|&b| match b {
+                    b' ' | b'!'..=b'~' => b as char,
+                    _ => '.',
+                })
+                .collect();
+            writeln!(out, "{:08x} {hex:<49}  |{ascii}|", row * 16)?;
+        }
+        return Ok(preview.len() as u64);
+    }
+
+    // Only a regular file's size is known without reading all of it.
+    // Anything else is read to find out, but only so far, since a device
+    // like /dev/zero or a pipe may never end.
+    let meta = file.get_ref().inner.metadata()?;
+    if meta.is_file() {
+        let size = meta.len().saturating_sub(file.stream_position()?);
+        writeln!(out, "(binary file, {size} bytes)")?;
+    } else {
+        let size = io::copy(&mut file.by_ref().take(BINARY_SIZE_LIMIT + 1),
+                            &mut io::sink())?;
+        if size > BINARY_SIZE_LIMIT {
+            writeln!(out, "(binary file, more than {BINARY_SIZE_LIMIT} bytes)")?;
+        } else {
+            writeln!(out, "(binary file, {size} bytes)")?;
+        }
+    }
+    // nothing was printed, so leave a seekable stdin where it was.
+    Ok(0)
+}
+
+
+/// Counts the bytes and lines going through a reader or writer, for
+/// --stats.
+struct Tally<T> {
+    inner: T,
+    delimiter: u8,
+    bytes: u64,
+    delimiters: u64,
+    /// The last byte seen, if any.
+    last: Option<u8>,
+}
+
+impl<T> Tally<T> {
+    fn new(inner: T, delimiter: u8) -> Tally<T> {
+        Tally { inner, delimiter, bytes: 0, delimiters: 0, last: None }
+    }
+
+    fn count(&mut self, data: &[u8]) {
+        self.bytes += data.len() as u64;
+        let delimiters = data.iter().filter(|&&b| b == self.delimiter).count();
+        self.delimiters += delimiters as u64;
+        if let Some(&last) = data.last() {
+            self.last = Some(last);
+        }
+    }
+
+    /// Lines seen, including a last one with no delimiter after it.
+    fn lines(&self) -> u64 {
+        let unterminated = self.last.is_some_and(|b| b != self.delimiter);
+        self.delimiters + unterminated as u64
+    }
+}
+
+impl<R: Read> Read for Tally<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.count(&buf[..len]);
+        Ok(len)
+    }
+}
+
+impl<W: Write> Write for Tally<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.count(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<S: Seek> Seek for Tally<S> {
+    // Seeking skips over data without it being counted; only regular files
+    // are seeked, and their totals are counted separately.
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+
+fn count_file(filename: &str, start: u64, delimiter: u8)
+    -> io::Result<(u64, u64)> {
+    // Count the lines and bytes in a regular file from start on. Opening
+    // stdin again through /dev/stdin gives it an offset of its own.
+    let path = if filename == "-" { "/dev/stdin" } else { filename };
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut tally = Tally::new(file, delimiter);
+    io::copy(&mut tally, &mut io::sink())?;
+    Ok((tally.lines(), tally.bytes))
+}
+
+
+fn expand(args: &Args) -> (Vec<String>, bool) {
+    // With -r, replace each directory by the files under it, walked in
+    // sorted order. Hidden files and directories are left out unless
+    // --hidden is given, except for ones named on the command line. Also
+    // says whether any directory was walked, since then every file gets a
+    // header.
+    if !args.recursive {
+        return (args.files.clone(), false);
+    }
+    let mut files = Vec::new();
+    let mut walked = false;
+    for filename in &args.files {
+        if filename == "-" || !std::path::Path::new(filename).is_dir() {
+            files.push(filename.clone());
+            continue;
+        }
+        walked = true;
+        let mut walk = WalkDir::new(filename).sort_by_file_name();
+        if let Some(depth) = args.max_depth {
+            walk = walk.max_depth(depth);
+        }
+        let entries = walk.into_iter().filter_entry(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            entry.depth() == 0 || !((name.starts_with('.') && !args.hidden)
+                || args.exclude.iter().any(|glob| glob.matches(&name)))
+        });
+        for entry in entries {
+            match entry {
+                Err(err) => eprintln!("{err}"),
+                Ok(entry) if entry.file_type().is_dir() => {}
+                Ok(entry) => {
+                    let name = entry.file_name().to_string_lossy();
+                    if !args.include.is_empty()
+                        && !args.include.iter().any(|glob| glob.matches(&name)) {
+                        continue;
+                    }
+                    // Only regular files, or links to them, are printed. A
+                    // link to a directory isn't followed, so there's no
+                    // walking in circles, and opening a FIFO or a device
+                    // could wait for ever.
+                    let path = entry.path();
+                    match fs::metadata(path) {
+                        Ok(meta) if meta.is_file() => {
+                            files.push(path.display().to_string());
+                        }
+                        Ok(_) => eprintln!("{}: not a regular file, skipped",
+                                           path.display()),
+                        Err(err) => eprintln!("{}: {err}", path.display()),
+                    }
+                }
+            }
+        }
+    }
+    (files, walked)
+}
+
+
+fn run(args: Args) -> Result<bool> {
+    // One generator for all the files, so a seed fixes the whole output.
+    let mut rng = match args.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let records = if args.csv {
+        Records::Csv
+    } else if args.jsonl {
+        Records::Jsonl
+    } else {
+        Records::Lines
+    };
+    let (files, walked) = expand(&args);
+    let mode = LineMode {
+        delimiter: if args.zero_terminated { b'\0' } else { b'\n' },
+        max_width: args.max_line_width,
+    };
+    // whether every JSON Lines file held only valid records; files that
+    // can't be opened are just noted, as they always have been.
+    let mut all_ok = true;
+    for (file_count, filename) in files.iter().enumerate() {
+        match open_file(filename) {
             // If there is a problem opening the file, note it and move on.
             Err(err) => eprintln!("{filename}: {err}"),
-            Ok(mut current_file) => {
-                if args.files.len() > 1 {
-                    // if we have more than one file print the header, preceded
-                    // by a newline for every file but the first.
-                    println!("{}==> {filename} <==",
-                             if file_count > 0 {"\n"} else {""}
-                    );
+            Ok(file) => {
+                if args.verbose
+                    || ((files.len() > 1 || walked) && !args.quiet) {
+                    print_header(args.header_format.as_ref(), filename, file_count);
                 }
-                // READ BYTES
-                // if args.bytes has a value and is not None, read up to that
-                // value number of bytes and print what we read.
-                if let Some(num_bytes) = args.bytes {
-                    let mut buf = vec![0; num_bytes as usize];
-                    // Might not be enough bytes to read the desired number,
-                    // so we determine how many we actually read...
-                    let bytes_read = current_file.read(&mut buf)?;
-                    // ...and print out that many bytes as a lossy String.
-                    print!("{}", String::from_utf8_lossy(&buf[..bytes_read]));
-                /*
-                if args.bytes.is_some() {
-                    let mut buf = vec![0; args.bytes.unwrap() as usize];
-                    current_file.read(&mut buf)?;
-                    buf.retain(|n| *n != 0);
-                    print!("{}", String::from_utf8_lossy(&buf));
-                */
-                // READ LINES
-                // if we're not reading bytes, then we're reading lines.
+                // Remember where a seekable stdin starts so it can be left
+                // just past what we print, like GNU head does; that makes
+                // `{ headr -n 1; cat; } < file` hand the rest to cat.
+                let start = if filename == "-" {
+                    stdin_file().and_then(|mut f| f.stream_position()).ok()
+                } else {
+                    None
+                };
+                // if args.bytes has a value and is not None, print bytes;
+                // likewise for characters, graphemes, the patterns, the
+                // ranges and sampling. Otherwise we're printing lines.
+                // Binary files come before all of those, when they aren't
+                // to be treated as text. Both sides are counted for --stats.
+                let regular = file.metadata()?.is_file();
+                let mut current_file =
+                    BufReader::new(Tally::new(file, mode.delimiter));
+                let mut out = Tally::new(io::stdout().lock(), mode.delimiter);
+                let binary = args.binary_files != BinaryFiles::Text
+                    && is_binary(current_file.fill_buf()?);
+                let consumed = if binary {
+                    print_binary(&mut current_file, &mut out, args.binary_files)?
+                } else if let Some(count) = args.bytes {
+                    print_bytes(&mut current_file, &mut out, count)?
+                } else if let Some(num_chars) = args.chars {
+                    print_chars(&mut current_file, &mut out, num_chars, false)?
+                } else if let Some(num_graphemes) = args.graphemes {
+                    print_chars(&mut current_file, &mut out, num_graphemes, true)?
+                } else if let Some(regex) = &args.until {
+                    print_until(&mut current_file, &mut out, regex,
+                                args.match_count, false, mode)?
+                } else if let Some(regex) = &args.through {
+                    print_until(&mut current_file, &mut out, regex,
+                                args.match_count, true, mode)?
+                } else if let Some(ranges) = &args.range {
+                    print_line_ranges(&mut current_file, &mut out, ranges, mode)?
+                } else if let Some(ranges) = &args.bytes_range {
+                    print_byte_ranges(&mut current_file, &mut out, ranges)?
+                } else if let Some(num_lines) = args.sample {
+                    print_sample(&mut current_file, &mut out, num_lines,
+                                 args.keep_order, &mut rng, mode)?
                 } else {
-                    // let lines_in_file = args.lines.try_into().unwrap();
-                    // let mut line_count = 0;
-                    let mut buf = String::new();
-                    /*
-                    while let Ok(line) = current_file.read_line(&mut buf) {
-                        line_count += 1;
-                        if (line == 0) | (line_count > lines_in_file) {
-                            break;
+                    match print_lines(&mut current_file, &mut out, args.lines,
+                                      records, mode) {
+                        Ok(consumed) => consumed,
+                        // a bad record ends this file, but not the run.
+                        Err(err) => {
+                            eprintln!("{filename}: {err}");
+                            all_ok = false;
+                            continue;
                         }
-                    */
-                    // try to read the desired number of lines.
-                    for _ in 0..args.lines {
-                        // using read_line() instead of lines() to preserve
-                        // line endings.
-                        let line = current_file.read_line(&mut buf)?;
-                        // if we reach the end of the file before reading the
-                        // requested number of lines, break out of the loop,
-                        // we're done.
-                        if line == 0 {
-                            break;
+                    }
+                };
+                if let Some(start) = start {
+                    stdin_file()?.seek(SeekFrom::Start(start + consumed))?;
+                }
+
+                if let Some(stats) = args.stats {
+                    // A regular file is counted again from a handle of its
+                    // own, so it doesn't matter how far the printing read
+                    // or where it seeked to. Anything else has to be read
+                    // to the end now, through the tally on the input.
+                    let total = if regular {
+                        count_file(filename, start.unwrap_or(0), mode.delimiter)?
+                    } else {
+                        io::copy(&mut current_file, &mut io::sink())?;
+                        let input = current_file.get_ref();
+                        (input.lines(), input.bytes)
+                    };
+                    // the note or preview printed for a binary file isn't
+                    // any of its lines.
+                    let shown = if binary {
+                        (0, consumed)
+                    } else {
+                        (out.lines(), out.bytes)
+                    };
+                    let footer = format!(
+                        "{filename}: shown {} of {} lines, {} of {} bytes",
+                        shown.0, total.0, shown.1, total.1,
+                    );
+                    match stats {
+                        Stats::Stderr => eprintln!("{footer}"),
+                        Stats::Inline => {
+                            // the footer goes on a line of its own, even
+                            // after output that doesn't end in a newline.
+                            if out.last.is_some_and(|b| b != b'\n') {
+                                writeln!(out.inner)?;
+                            }
+                            writeln!(out.inner, "{footer}")?;
                         }
-                        print!("{}", buf);
-                        buf.clear();
                     }
                 }
             },
         }
     }
 
-    Ok(())
+    Ok(all_ok)
 }
 
 
 fn main() {
-    if let Err(e) = run(Args::parse()) {
-        eprintln!("{e}");
-        std::process::exit(1);
+    match run(Args::parse()) {
+        Ok(true) => {}
+        Ok(false) => {
+            let _ = io::stdout().flush();
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
     }
 }
@@ -1,10 +1,12 @@
 use anyhow::Result;
-use assert_cmd::Command;
+use assert_cmd::{cargo::CommandCargoExt, Command};
 use predicates::prelude::*;
 use pretty_assertions::assert_eq;
 use rand::{distributions::Alphanumeric, Rng};
 use std::fs::{self, File};
 use std::io::prelude::*;
+use std::thread;
+use std::time::{Duration, Instant};
 
 const PRG: &str = "headr";
 const EMPTY: &str = "./tests/inputs/empty.txt";
@@ -12,6 +14,15 @@ const ONE: &str = "./tests/inputs/one.txt";
 const TWO: &str = "./tests/inputs/two.txt";
 const THREE: &str = "./tests/inputs/three.txt";
 const TWELVE: &str = "./tests/inputs/twelve.txt";
+const THOUSAND: &str = "./tests/inputs/thousand.txt";
+const GRAPHEMES: &str = "./tests/inputs/graphemes.txt";
+const TRACE: &str = "./tests/inputs/trace.log";
+const CSV: &str = "./tests/inputs/records.csv";
+const JSONL: &str = "./tests/inputs/records.jsonl";
+const BAD_JSONL: &str = "./tests/inputs/bad.jsonl";
+const BINARY: &str = "./tests/inputs/binary.bin";
+const LONG: &str = "./tests/inputs/long.txt";
+const ZERO: &str = "./tests/inputs/zero.txt";
 
 // --------------------------------------------------
 fn random_string() -> String {
@@ -423,3 +434,1290 @@ fn multiple_files_c4() -> Result<()> {
         "tests/expected/all.c4.out",
     )
 }
+
+// --------------------------------------------------
+#[test]
+fn twelve_n0() -> Result<()> {
+    run(&[TWELVE, "-n", "0"], "tests/expected/twelve.txt.n0.out")
+}
+
+#[test]
+fn twelve_c0() -> Result<()> {
+    run(&[TWELVE, "-c", "0"], "tests/expected/twelve.txt.c0.out")
+}
+
+#[test]
+fn twelve_n_neg3() -> Result<()> {
+    run(&[TWELVE, "-n", "-3"], "tests/expected/twelve.txt.n-3.out")
+}
+
+#[test]
+fn twelve_c_neg5() -> Result<()> {
+    run(&[TWELVE, "-c", "-5"], "tests/expected/twelve.txt.c-5.out")
+}
+
+#[test]
+fn twelve_n_neg3_stdin() -> Result<()> {
+    run_stdin(&["-n", "-3"], TWELVE, "tests/expected/twelve.txt.n-3.out")
+}
+
+#[test]
+fn twelve_c_neg5_stdin() -> Result<()> {
+    run_stdin(&["-c", "-5"], TWELVE, "tests/expected/twelve.txt.c-5.out")
+}
+
+#[test]
+fn one_n_neg1() -> Result<()> {
+    run(&[ONE, "-n", "-1"], "tests/expected/one.txt.n-1.out")
+}
+
+#[test]
+fn one_c_neg2() -> Result<()> {
+    run(&[ONE, "-c", "-2"], "tests/expected/one.txt.c-2.out")
+}
+
+#[test]
+fn multiple_files_n_neg2() -> Result<()> {
+    run(
+        &["-n", "-2", EMPTY, ONE, TWO, THREE, TWELVE],
+        "tests/expected/all.n-2.out",
+    )
+}
+
+#[test]
+fn multiple_files_c_neg3() -> Result<()> {
+    run(
+        &[EMPTY, ONE, TWO, THREE, TWELVE, "-c", "-3"],
+        "tests/expected/all.c-3.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bytes_too_large() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "16E", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value '16E' for '--bytes <BYTES>': number too large, \
+            the maximum is 18446744073709551615",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn dies_lines_too_large() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "99999999999999999999", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value '99999999999999999999' for '--lines <LINES>': \
+            number too large",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn dies_bad_suffix() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "1Q", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value '1Q' for '--bytes <BYTES>': \
+            invalid digit found in string",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn thousand_c1k() -> Result<()> {
+    run(&[THOUSAND, "-c", "1K"], "tests/expected/thousand.txt.c1K.out")
+}
+
+#[test]
+fn thousand_c1kb() -> Result<()> {
+    run(&[THOUSAND, "-c", "1kB"], "tests/expected/thousand.txt.c1kB.out")
+}
+
+#[test]
+fn thousand_n1b() -> Result<()> {
+    run(&[THOUSAND, "-n", "1b"], "tests/expected/thousand.txt.n1b.out")
+}
+
+#[test]
+fn thousand_c_neg2kib() -> Result<()> {
+    run(
+        &[THOUSAND, "-c", "-2KiB"],
+        "tests/expected/thousand.txt.c-2KiB.out",
+    )
+}
+
+// --------------------------------------------------
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut data = vec![0; len];
+    rand::thread_rng().fill(&mut data[..]);
+    data
+}
+
+// --------------------------------------------------
+#[test]
+fn one_c1_raw() -> Result<()> {
+    // Half of a two-byte character comes out as is, not as U+FFFD.
+    let output = Command::cargo_bin(PRG)?.args([ONE, "-c", "1"]).output()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, vec![0xc3]);
+
+    Ok(())
+}
+
+#[test]
+fn binary_stdin_c() -> Result<()> {
+    // Large enough that the pipe hands the data over in several reads.
+    let input = random_bytes(300_000);
+    for num_bytes in [1, 4096, 65_537, 250_000, 300_000, 400_000] {
+        let output = Command::cargo_bin(PRG)?
+            .write_stdin(input.clone())
+            .args(["-c", &num_bytes.to_string()])
+            .output()?;
+        assert!(output.status.success());
+        let expected = &input[..num_bytes.min(input.len())];
+        assert!(output.stdout == expected, "wrong output for -c {num_bytes}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn binary_stdin_c_neg() -> Result<()> {
+    let input = random_bytes(300_000);
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(input.clone())
+        .args(["-c", "-100000"])
+        .output()?;
+    assert!(output.status.success());
+    assert!(output.stdout == input[..200_000]);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn twelve_c_huge() -> Result<()> {
+    // Nothing the size of the count gets allocated, so these just print
+    // the whole file.
+    run(&[TWELVE, "-c", "100000000000"], TWELVE)?;
+    run(&[TWELVE, "-c", "15E"], TWELVE)
+}
+
+#[test]
+fn twelve_c_huge_stdin() -> Result<()> {
+    run_stdin(&["-c", "18446744073709551615"], TWELVE, TWELVE)
+}
+
+#[test]
+fn multiple_files_c_huge() -> Result<()> {
+    run(
+        &["-c", "1E", EMPTY, ONE, TWO, THREE, TWELVE],
+        "tests/expected/all.c1E.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files_n2_quiet() -> Result<()> {
+    run(
+        &["-q", "-n", "2", EMPTY, ONE, TWO, THREE, TWELVE],
+        "tests/expected/all.n2.q.out",
+    )
+}
+
+#[test]
+fn twelve_verbose() -> Result<()> {
+    run(&["-v", TWELVE], "tests/expected/twelve.txt.v.out")
+}
+
+#[test]
+fn twelve_quiet_then_verbose() -> Result<()> {
+    // As with GNU head, whichever comes last wins.
+    run(&["-q", "-v", TWELVE], "tests/expected/twelve.txt.v.out")?;
+    run(&["--verbose", "--silent", TWELVE], "tests/expected/twelve.txt.out")
+}
+
+#[test]
+fn multiple_files_header_format() -> Result<()> {
+    run(
+        &[
+            "-n",
+            "1",
+            "--header-format",
+            "### {name} ({size} bytes) [{index}]",
+            EMPTY,
+            ONE,
+            TWO,
+            THREE,
+            TWELVE,
+        ],
+        "tests/expected/all.n1.header-format.out",
+    )
+}
+
+#[test]
+fn header_format_mtime() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-v", "-n", "0", "--header-format", "{{{mtime}}}", ONE])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(
+            r"^\{\d{4}-\d\d-\d\dT\d\d:\d\d:\d\dZ\}\n$",
+        )?);
+
+    Ok(())
+}
+
+#[test]
+fn dies_bad_header_format() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--header-format", "{name} {lines}", ONE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value '{name} {lines}' for '--header-format <FORMAT>': \
+            unknown placeholder '{lines}'",
+        ));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_seekable_stdin(args: &[&str], input_file: &str) -> Result<String> {
+    // Run with a regular file as stdin and return what is left of it
+    // afterwards, as a following `cat` would see it.
+    let mut input = File::open(input_file)?;
+    let status = std::process::Command::cargo_bin(PRG)?
+        .args(args)
+        .stdin(input.try_clone()?)
+        .stdout(std::process::Stdio::null())
+        .status()?;
+    assert!(status.success());
+
+    let mut rest = String::new();
+    input.read_to_string(&mut rest)?;
+    Ok(rest)
+}
+
+#[test]
+fn stdin_offset_after_lines() -> Result<()> {
+    let rest = run_seekable_stdin(&["-n", "2"], TWELVE)?;
+    let expected: String = fs::read_to_string(TWELVE)?
+        .lines()
+        .skip(2)
+        .map(|line| format!("{line}\n"))
+        .collect();
+    assert_eq!(rest, expected);
+
+    Ok(())
+}
+
+#[test]
+fn stdin_offset_after_bytes() -> Result<()> {
+    let rest = run_seekable_stdin(&["-c", "5"], TWELVE)?;
+    assert_eq!(rest, fs::read_to_string(TWELVE)?[5..]);
+
+    Ok(())
+}
+
+#[test]
+fn stdin_offset_after_all_but_last() -> Result<()> {
+    let rest = run_seekable_stdin(&["-n", "-3"], TWELVE)?;
+    assert_eq!(rest, "ten\neleven\ntwelve\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_chars_and_lines() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--chars", "2", "-n", "1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the argument '--chars <CHARS>' cannot be used with \
+            '--lines <LINES>'",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn dies_graphemes_and_bytes() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--graphemes", "1", "-c", "2"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the argument '--graphemes <GRAPHEMES>' cannot be used with \
+            '--bytes <BYTES>'",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn graphemes_chars2() -> Result<()> {
+    // Stops inside the emoji sequence, but never inside a single emoji.
+    run(&[GRAPHEMES, "--chars", "2"], "tests/expected/graphemes.txt.chars2.out")
+}
+
+#[test]
+fn graphemes_graphemes2() -> Result<()> {
+    run(
+        &[GRAPHEMES, "--graphemes", "2"],
+        "tests/expected/graphemes.txt.graphemes2.out",
+    )
+}
+
+#[test]
+fn graphemes_chars30() -> Result<()> {
+    run(
+        &[GRAPHEMES, "--chars", "30"],
+        "tests/expected/graphemes.txt.chars30.out",
+    )
+}
+
+#[test]
+fn graphemes_graphemes30() -> Result<()> {
+    run(
+        &[GRAPHEMES, "--graphemes", "30"],
+        "tests/expected/graphemes.txt.graphemes30.out",
+    )
+}
+
+#[test]
+fn graphemes_graphemes2_stdin() -> Result<()> {
+    let input = fs::read(GRAPHEMES)?;
+    let expected = fs::read("tests/expected/graphemes.txt.graphemes2.out")?;
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .args(["--graphemes", "2"])
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+
+    Ok(())
+}
+
+#[test]
+fn multiple_files_graphemes3() -> Result<()> {
+    run(
+        &["--graphemes", "3", EMPTY, ONE, TWO, THREE, TWELVE],
+        "tests/expected/all.graphemes3.out",
+    )
+}
+
+#[test]
+fn dies_until_and_through() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--until", "a", "--through", "b", TRACE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the argument '--until <REGEX>' cannot be used with \
+            '--through <REGEX>'",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn dies_bad_regex() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--until", "(", TRACE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value '(' for '--until <REGEX>'",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn dies_match_count_without_pattern() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--match-count", "2", TRACE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the following required arguments were not provided",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn trace_until() -> Result<()> {
+    run(&[TRACE, "--until", "^Traceback"], "tests/expected/trace.log.until.out")
+}
+
+#[test]
+fn trace_until_stdin() -> Result<()> {
+    run_stdin(
+        &["--until", "^Traceback"],
+        TRACE,
+        "tests/expected/trace.log.until.out",
+    )
+}
+
+#[test]
+fn trace_through() -> Result<()> {
+    run(&[TRACE, "--through", "ERROR"], "tests/expected/trace.log.through.out")
+}
+
+#[test]
+fn trace_through_match_count() -> Result<()> {
+    run(
+        &[TRACE, "--through", "ERROR", "--match-count", "2"],
+        "tests/expected/trace.log.through.m2.out",
+    )
+}
+
+#[test]
+fn trace_until_no_match() -> Result<()> {
+    // Without a match the whole file is printed.
+    run(&[TRACE, "--until", "FATAL"], TRACE)
+}
+
+#[test]
+fn multiple_files_through() -> Result<()> {
+    run(
+        &["--through", "r", EMPTY, ONE, TWO, THREE, TWELVE],
+        "tests/expected/all.through.out",
+    )
+}
+
+#[test]
+fn stdin_offset_after_until() -> Result<()> {
+    // The matching line is left for whatever reads stdin next.
+    let rest = run_seekable_stdin(&["--until", "^Traceback"], TRACE)?;
+    let expected: String = fs::read_to_string(TRACE)?
+        .lines()
+        .skip(4)
+        .map(|line| format!("{line}\n"))
+        .collect();
+    assert_eq!(rest, expected);
+
+    Ok(())
+}
+
+#[test]
+fn through_stops_on_endless_input() -> Result<()> {
+    // stdin is never closed, so this only finishes if headr stops reading
+    // at the match.
+    let mut child = std::process::Command::cargo_bin(PRG)?
+        .args(["--through", "^STOP$"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("piped stdin");
+    stdin.write_all(b"one\nSTOP\ntwo\n")?;
+    stdin.flush()?;
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while child.try_wait()?.is_none() {
+        if Instant::now() > deadline {
+            child.kill()?;
+            panic!("headr kept reading after the match");
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    let mut output = String::new();
+    child.stdout.take().expect("piped stdout").read_to_string(&mut output)?;
+    assert_eq!(output, "one\nSTOP\n");
+    drop(stdin);
+
+    Ok(())
+}
+
+#[test]
+fn dies_decreasing_range() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--range", "3-2", TWELVE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value '3-2' for '--range <RANGES>': \
+            invalid decreasing range '3-2'",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn dies_range_zero() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--bytes-range", "0-4", TWELVE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("numbered from 1"));
+
+    Ok(())
+}
+
+#[test]
+fn dies_range_and_lines() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--range", "1-2", "-n", "1", TWELVE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the argument '--range <RANGES>' cannot be used with \
+            '--lines <LINES>'",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn twelve_range() -> Result<()> {
+    run(
+        &[TWELVE, "--range", "2-3,11-"],
+        "tests/expected/twelve.txt.range2-3,11-.out",
+    )
+}
+
+#[test]
+fn twelve_range_unordered() -> Result<()> {
+    // Ranges are printed in file order, and overlaps only once.
+    run(
+        &[TWELVE, "--range", "11-,3,2-3"],
+        "tests/expected/twelve.txt.range2-3,11-.out",
+    )
+}
+
+#[test]
+fn twelve_range_stdin() -> Result<()> {
+    run_stdin(
+        &["--range", "2-3,11-"],
+        TWELVE,
+        "tests/expected/twelve.txt.range2-3,11-.out",
+    )
+}
+
+#[test]
+fn twelve_bytes_range() -> Result<()> {
+    run(
+        &[TWELVE, "--bytes-range", "1-3,9-12"],
+        "tests/expected/twelve.txt.bytes-range1-3,9-12.out",
+    )
+}
+
+#[test]
+fn twelve_bytes_range_stdin() -> Result<()> {
+    run_stdin(
+        &["--bytes-range", "1-3,9-12"],
+        TWELVE,
+        "tests/expected/twelve.txt.bytes-range1-3,9-12.out",
+    )
+}
+
+#[test]
+fn bytes_range_past_end() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--bytes-range", "1000-", TWELVE])
+        .assert()
+        .success()
+        .stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn bytes_range_start_at_max() -> Result<()> {
+    // A start too far out to seek to is still just past the end.
+    Command::cargo_bin(PRG)?
+        .args(["--bytes-range", "18446744073709551615-", TWELVE])
+        .assert()
+        .success()
+        .stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn range_end_at_max() -> Result<()> {
+    // Merging with a range that ends at the largest number doesn't
+    // overflow.
+    run(&["--range", "1-18446744073709551615,5", TWELVE], TWELVE)
+}
+
+#[test]
+fn multiple_files_range() -> Result<()> {
+    run(
+        &["--range", "1,3-", EMPTY, ONE, TWO, THREE, TWELVE],
+        "tests/expected/all.range1,3-.out",
+    )
+}
+
+#[test]
+fn stdin_offset_after_range() -> Result<()> {
+    // Reading stops at the end of the last range.
+    let rest = run_seekable_stdin(&["--range", "2-3"], TWELVE)?;
+    let expected: String = fs::read_to_string(TWELVE)?
+        .lines()
+        .skip(3)
+        .map(|line| format!("{line}\n"))
+        .collect();
+    assert_eq!(rest, expected);
+
+    Ok(())
+}
+
+#[test]
+fn stdin_offset_after_bytes_range() -> Result<()> {
+    let rest = run_seekable_stdin(&["--bytes-range", "2-3,6"], TWELVE)?;
+    assert_eq!(rest, fs::read_to_string(TWELVE)?[6..]);
+
+    Ok(())
+}
+
+#[test]
+fn bytes_range_seeks_huge_sparse_file() -> Result<()> {
+    // 50 GB of holes followed by a few bytes. This only finishes in time if
+    // headr seeks to the range instead of reading up to it.
+    let path = std::env::temp_dir().join(format!("headr-{}", random_string()));
+    let mut file = File::create_new(&path)?;
+    let hole: u64 = 50 * 1024 * 1024 * 1024;
+    file.seek(std::io::SeekFrom::Start(hole))?;
+    file.write_all(b"found it\n")?;
+    drop(file);
+
+    let start = format!("{}-", hole + 1);
+    let output = Command::cargo_bin(PRG)?
+        .args(["--bytes-range", &start, path.to_str().unwrap()])
+        .timeout(Duration::from_secs(10))
+        .output();
+    let _ = fs::remove_file(&path);
+    let output = output?;
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout)?, "found it\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+fn sample(args: &[&str]) -> Result<Vec<String>> {
+    let output = Command::cargo_bin(PRG)?.args(args).output()?;
+    assert!(output.status.success());
+    Ok(String::from_utf8(output.stdout)?
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
+#[test]
+fn dies_seed_without_sample() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--seed", "1", TWELVE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the following required arguments were not provided",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn sample_is_reproducible_with_seed() -> Result<()> {
+    let first = sample(&["--sample", "10", "--seed", "42", THOUSAND])?;
+    let second = sample(&["--sample", "10", "--seed", "42", THOUSAND])?;
+    assert_eq!(first.len(), 10);
+    assert_eq!(first, second);
+
+    Ok(())
+}
+
+#[test]
+fn sample_lines_come_from_input() -> Result<()> {
+    let input = fs::read_to_string(THOUSAND)?;
+    let lines: Vec<&str> = input.lines().collect();
+    let picked = sample(&["--sample", "25", THOUSAND])?;
+    assert_eq!(picked.len(), 25);
+    for line in &picked {
+        assert!(lines.contains(&line.as_str()));
+    }
+    let mut unique = picked.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(unique.len(), 25);
+
+    Ok(())
+}
+
+#[test]
+fn sample_keep_order() -> Result<()> {
+    let picked = sample(&["--sample", "25", "--keep-order", THOUSAND])?;
+    let numbers: Vec<u32> = picked.iter().map(|l| l.parse().unwrap()).collect();
+    assert_eq!(numbers.len(), 25);
+    assert!(numbers.windows(2).all(|pair| pair[0] < pair[1]));
+
+    Ok(())
+}
+
+#[test]
+fn sample_more_than_lines() -> Result<()> {
+    // Asking for more lines than there are gets every line.
+    run(&["--sample", "20", "--keep-order", TWELVE], TWELVE)
+}
+
+#[test]
+fn sample_covers_whole_input() -> Result<()> {
+    // Across seeds, both early and late lines get picked, so the sample
+    // isn't just the start of the file.
+    let mut picked = Vec::new();
+    for seed in 0..10 {
+        let seed = seed.to_string();
+        picked.extend(sample(&["--sample", "5", "--seed", &seed, THOUSAND])?);
+    }
+    let numbers: Vec<u32> = picked.iter().map(|l| l.parse().unwrap()).collect();
+    assert!(numbers.iter().any(|&n| n <= 500));
+    assert!(numbers.iter().any(|&n| n > 500));
+
+    Ok(())
+}
+
+#[test]
+fn multiple_files_sample() -> Result<()> {
+    let output = sample(&["--sample", "2", "--seed", "7", ONE, TWELVE])?;
+    assert_eq!(output.len(), 6);
+    assert_eq!(output[0], "==> ./tests/inputs/one.txt <==");
+    assert_eq!(output[2], "");
+    assert_eq!(output[3], "==> ./tests/inputs/twelve.txt <==");
+
+    Ok(())
+}
+
+#[test]
+fn dies_csv_and_jsonl() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--csv", "--jsonl", CSV])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the argument '--csv' cannot be used with '--jsonl'",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn csv_n3() -> Result<()> {
+    // Quoted fields spanning lines stay whole, and the header row doesn't
+    // count.
+    run(&[CSV, "--csv", "-n", "3"], "tests/expected/records.csv.n3.out")
+}
+
+#[test]
+fn csv_n3_stdin() -> Result<()> {
+    run_stdin(&["--csv", "-n", "3"], CSV, "tests/expected/records.csv.n3.out")
+}
+
+#[test]
+fn csv_n0_keeps_header() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--csv", "-n", "0", CSV])
+        .assert()
+        .success()
+        .stdout("id,name,notes\n");
+
+    Ok(())
+}
+
+#[test]
+fn csv_all_but_last() -> Result<()> {
+    run(&[CSV, "--csv", "-n", "-1"], "tests/expected/records.csv.n-1.out")
+}
+
+#[test]
+fn jsonl_n3() -> Result<()> {
+    run(&[JSONL, "--jsonl", "-n", "3"], "tests/expected/records.jsonl.n3.out")
+}
+
+#[test]
+fn jsonl_stops_before_bad_record() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--jsonl", BAD_JSONL])
+        .assert()
+        .failure()
+        .stdout("{\"id\":1}\n{\"id\":2}\n")
+        .stderr(predicate::str::starts_with(format!(
+            "{BAD_JSONL}: record 3 is not valid JSON"
+        )));
+
+    Ok(())
+}
+
+#[test]
+fn jsonl_bad_record_moves_on() -> Result<()> {
+    // A bad record ends its own file; the files after it are still shown.
+    Command::cargo_bin(PRG)?
+        .args(["--jsonl", "-q", "-n", "3", BAD_JSONL, JSONL])
+        .assert()
+        .failure()
+        .stdout(format!("{{\"id\":1}}\n{{\"id\":2}}\n{}",
+                        fs::read_to_string("tests/expected/records.jsonl.n3.out")?))
+        .stderr(predicate::str::starts_with(format!(
+            "{BAD_JSONL}: record 3 is not valid JSON"
+        )));
+
+    Ok(())
+}
+
+#[test]
+fn jsonl_blank_line_rejected() -> Result<()> {
+    // A blank line isn't a JSON Lines record.
+    Command::cargo_bin(PRG)?
+        .write_stdin("{\"a\":1}\n\n{\"b\":2}\n")
+        .arg("--jsonl")
+        .assert()
+        .failure()
+        .stdout("{\"a\":1}\n")
+        .stderr("-: record 2 is blank\n");
+
+    Ok(())
+}
+
+#[test]
+fn jsonl_bad_record_not_printed() -> Result<()> {
+    // Only the records that get printed are checked.
+    Command::cargo_bin(PRG)?
+        .args(["--jsonl", "-n", "2", BAD_JSONL])
+        .assert()
+        .success()
+        .stdout("{\"id\":1}\n{\"id\":2}\n");
+
+    Ok(())
+}
+
+#[test]
+fn binary_files_text_is_default() -> Result<()> {
+    let expected = fs::read(BINARY)?;
+    let output = Command::cargo_bin(PRG)?.args(["-c", "200", BINARY]).output()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+
+    Ok(())
+}
+
+#[test]
+fn binary_files_skip() -> Result<()> {
+    run(
+        &["--binary-files=skip", ONE, BINARY, TWO],
+        "tests/expected/mixed.skip.out",
+    )
+}
+
+#[test]
+fn binary_files_skip_stdin() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .write_stdin(fs::read(BINARY)?)
+        .args(["--binary-files", "skip"])
+        .assert()
+        .success()
+        .stdout("(binary file, 200 bytes)\n");
+
+    Ok(())
+}
+
+#[test]
+fn binary_files_skip_endless_device() -> Result<()> {
+    // A character device that never ends is only read so far.
+    Command::cargo_bin(PRG)?
+        .args(["--binary-files=skip", "/dev/zero"])
+        .timeout(std::time::Duration::from_secs(20))
+        .assert()
+        .success()
+        .stdout("(binary file, more than 1048576 bytes)\n");
+
+    Ok(())
+}
+
+#[test]
+fn binary_files_hex() -> Result<()> {
+    run(
+        &["--binary-files", "hex", BINARY],
+        "tests/expected/binary.bin.hex.out",
+    )
+}
+
+#[test]
+fn binary_files_utf8_is_text() -> Result<()> {
+    run(
+        &["--binary-files", "skip", GRAPHEMES, "--chars", "30"],
+        "tests/expected/graphemes.txt.chars30.out",
+    )
+}
+
+#[test]
+fn binary_files_control_characters() -> Result<()> {
+    // No NUL bytes, but mostly control characters.
+    Command::cargo_bin(PRG)?
+        .write_stdin("\x01\x02\x03\x04\x05ab\x06\x07\x08")
+        .args(["--binary-files", "skip"])
+        .assert()
+        .success()
+        .stdout("(binary file, 10 bytes)\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_tree(args: &[&str]) -> Result<String> {
+    let output = run_tree_output(args)?;
+    assert!(output.status.success());
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+fn run_tree_output(args: &[&str]) -> Result<std::process::Output> {
+    // Run in a fresh directory holding a small tree of files, some hidden,
+    // along with a link to a directory and a FIFO, which are never printed.
+    let dir = std::env::temp_dir().join(format!("headr-{}", random_string()));
+    let files = [
+        ("b.txt", "b1\nb2\n"),
+        ("a.csv", "x,y\n1,2\n"),
+        (".hidden.txt", "secret\n"),
+        ("sub/c.txt", "c1\nc2\n"),
+        ("sub/deeper/d.log", "d1\n"),
+        (".git/config", "[core]\n"),
+        ("target/e.txt", "e1\n"),
+    ];
+    for (name, contents) in files {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, contents)?;
+    }
+    std::os::unix::fs::symlink("deeper", dir.join("sub/link"))?;
+    let made_fifo = std::process::Command::new("mkfifo")
+        .arg(dir.join("sub/pipe"))
+        .status()?;
+    assert!(made_fifo.success());
+    let output = Command::cargo_bin(PRG)?
+        .current_dir(&dir)
+        .args(args)
+        .timeout(std::time::Duration::from_secs(20))
+        .output()?;
+    fs::remove_dir_all(&dir)?;
+    Ok(output)
+}
+
+#[test]
+fn dies_include_without_recursive() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--include", "*.txt", TWELVE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the following required arguments were not provided",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn recursive() -> Result<()> {
+    let output = run_tree(&["-r", "-n", "1", "."])?;
+    assert_eq!(
+        output,
+        "==> ./a.csv <==\nx,y\n\n\
+        ==> ./b.txt <==\nb1\n\n\
+        ==> ./sub/c.txt <==\nc1\n\n\
+        ==> ./sub/deeper/d.log <==\nd1\n\n\
+        ==> ./target/e.txt <==\ne1\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn recursive_skips_links_to_dirs_and_fifos() -> Result<()> {
+    // Neither stops the run; they are noted and the rest is printed.
+    let output = run_tree_output(&["-r", "-q", "-n", "1", "sub"])?;
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout)?, "c1\nd1\n");
+    assert_eq!(
+        String::from_utf8(output.stderr)?,
+        "sub/link: not a regular file, skipped\n\
+         sub/pipe: not a regular file, skipped\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn recursive_hidden() -> Result<()> {
+    let output = run_tree(&["-r", "-q", "--hidden", "--exclude", "sub", "."])?;
+    assert_eq!(output, "[core]\nsecret\nx,y\n1,2\nb1\nb2\ne1\n");
+
+    Ok(())
+}
+
+#[test]
+fn recursive_include() -> Result<()> {
+    let output =
+        run_tree(&["-r", "-n", "1", "--include", "*.txt", "sub", "b.txt"])?;
+    assert_eq!(output, "==> sub/c.txt <==\nc1\n\n==> b.txt <==\nb1\n");
+
+    Ok(())
+}
+
+#[test]
+fn recursive_exclude() -> Result<()> {
+    // Excluding a directory skips everything under it.
+    let output = run_tree(&[
+        "-r", "-q", "-n", "1", "--exclude", "target", "--exclude", "*.csv", ".",
+    ])?;
+    assert_eq!(output, "b1\nc1\nd1\n");
+
+    Ok(())
+}
+
+#[test]
+fn recursive_max_depth() -> Result<()> {
+    let output = run_tree(&["-r", "-q", "-n", "1", "--max-depth", "2", "."])?;
+    assert_eq!(output, "x,y\nb1\nc1\ne1\n");
+
+    Ok(())
+}
+
+#[test]
+fn recursive_hidden_root() -> Result<()> {
+    // A hidden directory named on the command line is still walked, and
+    // its one file still gets a header.
+    let output = run_tree(&["-r", ".git"])?;
+    assert_eq!(output, "==> .git/config <==\n[core]\n");
+    let output = run_tree(&["-r", "-q", ".git"])?;
+    assert_eq!(output, "[core]\n");
+
+    Ok(())
+}
+
+#[test]
+fn max_line_width() -> Result<()> {
+    run(
+        &[LONG, "--max-line-width", "20"],
+        "tests/expected/long.txt.max-line-width20.out",
+    )
+}
+
+#[test]
+fn max_line_width_multiple_files() -> Result<()> {
+    run(
+        &["--max-line-width", "12", "-n", "3", LONG, TWELVE],
+        "tests/expected/all.max-line-width12.out",
+    )
+}
+
+#[test]
+fn max_line_width_keeps_graphemes() -> Result<()> {
+    // The family emoji would take columns 6 and 7, so it goes whole.
+    Command::cargo_bin(PRG)?
+        .write_stdin("café 👩‍👩‍👧 family\n")
+        .args(["--max-line-width", "6"])
+        .assert()
+        .success()
+        .stdout("café …[25 bytes omitted]\n");
+
+    Ok(())
+}
+
+#[test]
+fn max_line_width_expands_tabs() -> Result<()> {
+    // The tab runs to column 8, past the limit; the bell takes no room.
+    Command::cargo_bin(PRG)?
+        .write_stdin("ab\tcdefghij\n\x07ring\n")
+        .args(["--max-line-width", "4"])
+        .assert()
+        .success()
+        .stdout("ab…[9 bytes omitted]\n\x07ring\n");
+
+    Ok(())
+}
+
+#[test]
+fn max_line_width_with_range() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--max-line-width", "3", "--range", "11-", TWELVE])
+        .assert()
+        .success()
+        .stdout("ele…[3 bytes omitted]\ntwe…[3 bytes omitted]\n");
+
+    Ok(())
+}
+
+#[test]
+fn dies_max_line_width_and_bytes() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--max-line-width", "3", "-c", "4", TWELVE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the argument '--max-line-width <COLS>' cannot be used with \
+            '--bytes <BYTES>'",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn zero_terminated_n2() -> Result<()> {
+    // The first path has a newline in it.
+    run(&[ZERO, "-z", "-n", "2"], "tests/expected/zero.txt.z.n2.out")
+}
+
+#[test]
+fn zero_terminated_n2_stdin() -> Result<()> {
+    run_stdin(
+        &["--zero-terminated", "-n", "2"],
+        ZERO,
+        "tests/expected/zero.txt.z.n2.out",
+    )
+}
+
+#[test]
+fn zero_terminated_all_but_last() -> Result<()> {
+    run(&[ZERO, "-z", "-n", "-1"], "tests/expected/zero.txt.z.n-1.out")
+}
+
+#[test]
+fn zero_terminated_multiple_files() -> Result<()> {
+    // Headers still end with newlines, as in GNU head.
+    run(&["-z", "-n", "1", ZERO, TWELVE], "tests/expected/all.z.n1.out")
+}
+
+#[test]
+fn zero_terminated_until() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-z", "--until", "^./b", ZERO])
+        .assert()
+        .success()
+        .stdout("./a\n./with\nnewline\0");
+
+    Ok(())
+}
+
+#[test]
+fn stats_stderr() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--stats", "-n", "3", TWELVE])
+        .assert()
+        .success()
+        .stdout("one\ntwo\nthree\n")
+        .stderr(format!("{TWELVE}: shown 3 of 12 lines, 14 of 63 bytes\n"));
+
+    Ok(())
+}
+
+#[test]
+fn stats_max_line_width() -> Result<()> {
+    // Only the input that was shown counts, not the markers for what was
+    // cut.
+    Command::cargo_bin(PRG)?
+        .args(["--stats", "--max-line-width", "5", "-n", "1", LONG])
+        .assert()
+        .success()
+        .stdout("short…[5 bytes omitted]\n")
+        .stderr(format!("{LONG}: shown 1 of 5 lines, 6 of 2101 bytes\n"));
+
+    Ok(())
+}
+
+#[test]
+fn stats_inline_multiple_files() -> Result<()> {
+    run(
+        &["--stats=inline", "-n", "2", TWELVE, EMPTY, THOUSAND],
+        "tests/expected/all.n2.stats-inline.out",
+    )
+}
+
+#[test]
+fn stats_stdin_pipe() -> Result<()> {
+    // A pipe can't be read again, so headr reads on to the end to count it.
+    Command::cargo_bin(PRG)?
+        .write_stdin(fs::read(THOUSAND)?)
+        .args(["--stats", "-c", "10"])
+        .assert()
+        .success()
+        .stdout("1\n2\n3\n4\n5\n")
+        .stderr("-: shown 5 of 1000 lines, 10 of 3893 bytes\n");
+
+    Ok(())
+}
+
+#[test]
+fn stats_all_but_last() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .write_stdin("1\n2\n3\n4\n5\n")
+        .args(["--stats", "-n", "-2"])
+        .assert()
+        .success()
+        .stdout("1\n2\n3\n")
+        .stderr("-: shown 3 of 5 lines, 6 of 10 bytes\n");
+
+    Ok(())
+}
+
+#[test]
+fn stats_inline_unterminated() -> Result<()> {
+    // The footer starts a new line, and the last line counts even without
+    // a newline after it.
+    Command::cargo_bin(PRG)?
+        .write_stdin("a\nb")
+        .args(["--stats=inline", "-c", "3"])
+        .assert()
+        .success()
+        .stdout("a\nb\n-: shown 2 of 2 lines, 3 of 3 bytes\n");
+
+    Ok(())
+}
+
+#[test]
+fn stats_seekable_stdin() -> Result<()> {
+    // Counted from where stdin was, and stdin is still left just past what
+    // was printed.
+    let mut input = File::open(TWELVE)?;
+    input.seek(std::io::SeekFrom::Start(4))?;
+    let output = std::process::Command::cargo_bin(PRG)?
+        .args(["--stats", "-n", "1"])
+        .stdin(input.try_clone()?)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout)?, "two\n");
+    assert_eq!(
+        String::from_utf8(output.stderr)?,
+        "-: shown 1 of 11 lines, 4 of 59 bytes\n"
+    );
+    let mut rest = String::new();
+    input.read_to_string(&mut rest)?;
+    assert!(rest.starts_with("three\n"));
+
+    Ok(())
+}
+
+#[test]
+fn stats_binary_skip() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--stats", "--binary-files=skip", BINARY])
+        .assert()
+        .success()
+        .stdout("(binary file, 200 bytes)\n")
+        .stderr(format!("{BINARY}: shown 0 of 2 lines, 0 of 200 bytes\n"));
+
+    Ok(())
+}
@@ -0,0 +1,571 @@
+use anyhow::Result;
+use assert_cmd::{cargo::CommandCargoExt, Command};
+use predicates::prelude::*;
+use pretty_assertions::assert_eq;
+use rand::{distributions::Alphanumeric, Rng};
+use std::fs::{self, File, OpenOptions};
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const PRG: &str = "tailr";
+const EMPTY: &str = "./tests/inputs/empty.txt";
+const ONE: &str = "./tests/inputs/one.txt";
+const TWO: &str = "./tests/inputs/two.txt";
+const THREE: &str = "./tests/inputs/three.txt";
+const TWELVE: &str = "./tests/inputs/twelve.txt";
+const THOUSAND: &str = "./tests/inputs/thousand.txt";
+
+// --------------------------------------------------
+fn random_string() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(7)
+        .map(char::from)
+        .collect()
+}
+
+// --------------------------------------------------
+fn gen_bad_file() -> String {
+    loop {
+        let filename = random_string();
+        if fs::metadata(&filename).is_err() {
+            return filename;
+        }
+    }
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_lines() -> Result<()> {
+    let bad = random_string();
+    let expected = format!(
+        "error: invalid value '{bad}' for \
+        '--lines <LINES>': invalid digit found in string"
+    );
+    Command::cargo_bin(PRG)?
+        .args(["-n", &bad, EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(expected));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bytes_and_lines() -> Result<()> {
+    let msg = "the argument '--lines <LINES>' cannot be \
+               used with '--bytes <BYTES>'";
+
+    Command::cargo_bin(PRG)?
+        .args(["-n", "1", "-c", "2"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(msg));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn skips_bad_file() -> Result<()> {
+    let bad = gen_bad_file();
+    let expected = format!("{bad}: .* [(]os error 2[)]");
+    Command::cargo_bin(PRG)?
+        .args([EMPTY, &bad, ONE])
+        .assert()
+        .stderr(predicate::str::is_match(expected)?);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run(args: &[&str], expected_file: &str) -> Result<()> {
+    let expected = fs::read(expected_file)?;
+    let output = Command::cargo_bin(PRG)?.args(args).output().expect("fail");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&expected)
+    );
+
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_stdin(
+    args: &[&str],
+    input_file: &str,
+    expected_file: &str,
+) -> Result<()> {
+    // Piped input can't seek, so this exercises the ring buffer.
+    let expected = fs::read(expected_file)?;
+    let input = fs::read(input_file)?;
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .args(args)
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&expected)
+    );
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn empty() -> Result<()> {
+    run(&[EMPTY], "tests/expected/tailr/empty.txt.out")
+}
+
+#[test]
+fn empty_n3() -> Result<()> {
+    run(&[EMPTY, "-n", "3"], "tests/expected/tailr/empty.txt.n3.out")
+}
+
+#[test]
+fn empty_c5() -> Result<()> {
+    run(&[EMPTY, "-c", "5"], "tests/expected/tailr/empty.txt.c5.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn one() -> Result<()> {
+    run(&[ONE], "tests/expected/tailr/one.txt.out")
+}
+
+#[test]
+fn one_n3() -> Result<()> {
+    run(&[ONE, "-n", "3"], "tests/expected/tailr/one.txt.n3.out")
+}
+
+#[test]
+fn one_n_plus3() -> Result<()> {
+    run(&[ONE, "-n", "+3"], "tests/expected/tailr/one.txt.n+3.out")
+}
+
+#[test]
+fn one_c5() -> Result<()> {
+    run(&[ONE, "-c", "5"], "tests/expected/tailr/one.txt.c5.out")
+}
+
+#[test]
+fn one_c_plus5() -> Result<()> {
+    run(&[ONE, "-c", "+5"], "tests/expected/tailr/one.txt.c+5.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn two_n3() -> Result<()> {
+    run(&[TWO, "-n", "3"], "tests/expected/tailr/two.txt.n3.out")
+}
+
+#[test]
+fn two_c5_stdin() -> Result<()> {
+    run_stdin(&["-c", "5"], TWO, "tests/expected/tailr/two.txt.c5.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn three() -> Result<()> {
+    run(&[THREE], "tests/expected/tailr/three.txt.out")
+}
+
+#[test]
+fn three_n_plus3() -> Result<()> {
+    run(&[THREE, "-n", "+3"], "tests/expected/tailr/three.txt.n+3.out")
+}
+
+#[test]
+fn three_c_plus5_stdin() -> Result<()> {
+    run_stdin(&["-c", "+5"], THREE, "tests/expected/tailr/three.txt.c+5.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn twelve() -> Result<()> {
+    run(&[TWELVE], "tests/expected/tailr/twelve.txt.out")
+}
+
+#[test]
+fn twelve_n3() -> Result<()> {
+    run(&[TWELVE, "-n", "3"], "tests/expected/tailr/twelve.txt.n3.out")
+}
+
+#[test]
+fn twelve_n_minus3() -> Result<()> {
+    run(&[TWELVE, "-n", "-3"], "tests/expected/tailr/twelve.txt.n3.out")
+}
+
+#[test]
+fn twelve_n_plus3() -> Result<()> {
+    run(&[TWELVE, "-n", "+3"], "tests/expected/tailr/twelve.txt.n+3.out")
+}
+
+#[test]
+fn twelve_c5() -> Result<()> {
+    run(&[TWELVE, "-c", "5"], "tests/expected/tailr/twelve.txt.c5.out")
+}
+
+#[test]
+fn twelve_c_plus5() -> Result<()> {
+    run(&[TWELVE, "-c", "+5"], "tests/expected/tailr/twelve.txt.c+5.out")
+}
+
+#[test]
+fn twelve_stdin() -> Result<()> {
+    run_stdin(&[], TWELVE, "tests/expected/tailr/twelve.txt.out")
+}
+
+#[test]
+fn twelve_n3_stdin() -> Result<()> {
+    run_stdin(&["-n", "3"], TWELVE, "tests/expected/tailr/twelve.txt.n3.out")
+}
+
+#[test]
+fn twelve_n_plus3_stdin() -> Result<()> {
+    run_stdin(
+        &["-n", "+3"],
+        TWELVE,
+        "tests/expected/tailr/twelve.txt.n+3.out",
+    )
+}
+
+#[test]
+fn twelve_c5_stdin() -> Result<()> {
+    run_stdin(&["-c", "5"], TWELVE, "tests/expected/tailr/twelve.txt.c5.out")
+}
+
+#[test]
+fn twelve_verbose() -> Result<()> {
+    run(&["-v", TWELVE], "tests/expected/tailr/twelve.txt.v.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn thousand_n1k() -> Result<()> {
+    run(
+        &[THOUSAND, "-n", "1K"],
+        "tests/expected/tailr/thousand.txt.n1K.out",
+    )
+}
+
+#[test]
+fn thousand_c1b() -> Result<()> {
+    run(
+        &[THOUSAND, "-c", "1b"],
+        "tests/expected/tailr/thousand.txt.c1b.out",
+    )
+}
+
+#[test]
+fn thousand_c1b_stdin() -> Result<()> {
+    run_stdin(
+        &["-c", "1b"],
+        THOUSAND,
+        "tests/expected/tailr/thousand.txt.c1b.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files() -> Result<()> {
+    run(
+        &[EMPTY, ONE, TWO, THREE, TWELVE],
+        "tests/expected/tailr/all.out",
+    )
+}
+
+#[test]
+fn multiple_files_n2() -> Result<()> {
+    run(
+        &["-n", "2", EMPTY, ONE, TWO, THREE, TWELVE],
+        "tests/expected/tailr/all.n2.out",
+    )
+}
+
+#[test]
+fn multiple_files_c3() -> Result<()> {
+    run(
+        &[EMPTY, ONE, TWO, THREE, TWELVE, "-c", "3"],
+        "tests/expected/tailr/all.c3.out",
+    )
+}
+
+#[test]
+fn multiple_files_n1_quiet() -> Result<()> {
+    run(
+        &["-q", "-n", "1", EMPTY, ONE, TWO, THREE, TWELVE],
+        "tests/expected/tailr/all.n1.q.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn huge_sparse_file() -> Result<()> {
+    // 50 GB of holes followed by a few lines. Reading the whole file would
+    // take far too long, so this only passes if tailr seeks to the end.
+    let path = std::env::temp_dir().join(format!("tailr-{}", random_string()));
+    let mut file = OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .open(&path)?;
+    let hole = 50 * 1024 * 1024 * 1024;
+    file.seek(SeekFrom::Start(hole))?;
+    let tail: String = (1..=20).map(|n| format!("line {n}\n")).collect();
+    file.write_all(tail.as_bytes())?;
+    drop(file);
+
+    let output = Command::cargo_bin(PRG)?
+        .args(["-n", "3", path.to_str().unwrap()])
+        .timeout(std::time::Duration::from_secs(10))
+        .output();
+    let _ = fs::remove_file(&path);
+    let output = output?;
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        "line 18\nline 19\nline 20\n"
+    );
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn seekable_stdin_part_way() -> Result<()> {
+    // A file on stdin that has already been partly read only counts from
+    // where it was left.
+    let mut input = File::open(TWELVE)?;
+    input.seek(SeekFrom::Start(fs::read_to_string(TWELVE)?.len() as u64 - 4))?;
+    let output = std::process::Command::cargo_bin(PRG)?
+        .args(["-c", "+2"])
+        .stdin(input)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout)?, "ve\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_follow() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--follow=inode", ONE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value 'inode' for '--follow[=<HOW>]'",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn follow_pipe_ends() -> Result<()> {
+    // There is nothing to wait for on a pipe once it is read to the end.
+    run_stdin(
+        &["-f", "-n", "3"],
+        TWELVE,
+        "tests/expected/tailr/twelve.txt.n3.out",
+    )
+}
+
+// --------------------------------------------------
+struct FollowRun {
+    dir: std::path::PathBuf,
+    watched: Watched,
+    tailr: std::process::Child,
+    stdout: Output,
+    stderr: Output,
+}
+
+/// The process tailr is told to watch with --pid. Killing it, when the
+/// test ends or fails, is what stops tailr.
+struct Watched(std::process::Child);
+
+impl Drop for Watched {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+type Output = (Arc<Mutex<String>>, thread::JoinHandle<()>);
+
+fn collect(mut pipe: impl Read + Send + 'static) -> Output {
+    // Gather what tailr writes as it writes it, so tests can wait for it.
+    let text = Arc::new(Mutex::new(String::new()));
+    let sink = Arc::clone(&text);
+    let reader = thread::spawn(move || {
+        let mut buf = [0; 4096];
+        while let Ok(n @ 1..) = pipe.read(&mut buf) {
+            sink.lock().unwrap().push_str(&String::from_utf8_lossy(&buf[..n]));
+        }
+    });
+    (text, reader)
+}
+
+fn start_follow(args: &[&str], files: &[(&str, &str)]) -> Result<FollowRun> {
+    // Create the files in a fresh directory and start tailr on them in the
+    // background, following until a `sleep` that finish_follow kills exits.
+    let dir = std::env::temp_dir().join(format!("tailr-{}", random_string()));
+    fs::create_dir(&dir)?;
+    for (name, contents) in files {
+        fs::write(dir.join(name), contents)?;
+    }
+    let watched = Watched(
+        std::process::Command::new("sleep")
+            .arg("600")
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()?,
+    );
+    let mut tailr = std::process::Command::cargo_bin(PRG)?
+        .current_dir(&dir)
+        .args(["--pid", &watched.0.id().to_string(), "-s", "0.1"])
+        .args(args)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+    let stdout = collect(tailr.stdout.take().unwrap());
+    let stderr = collect(tailr.stderr.take().unwrap());
+    Ok(FollowRun { dir, watched, tailr, stdout, stderr })
+}
+
+fn wait_for(output: &Output, done: impl Fn(&str) -> bool) {
+    // Poll rather than sleep for a set time, so a slow machine only makes
+    // the test slower.
+    let deadline = Instant::now() + Duration::from_secs(30);
+    loop {
+        let text = output.0.lock().unwrap().clone();
+        if done(&text) {
+            return;
+        }
+        assert!(Instant::now() < deadline, "timed out; got {text:?}");
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+fn wait_stdout(run: &FollowRun, end: &str) {
+    wait_for(&run.stdout, |text| text.ends_with(end));
+}
+
+fn wait_stderr(run: &FollowRun, part: &str) {
+    wait_for(&run.stderr, |text| text.contains(part));
+}
+
+fn finish_follow(mut run: FollowRun) -> Result<(String, String)> {
+    drop(run.watched);
+    let status = run.tailr.wait()?;
+    fs::remove_dir_all(&run.dir)?;
+    assert!(status.success());
+    let mut output = Vec::new();
+    for (text, reader) in [run.stdout, run.stderr] {
+        reader.join().unwrap();
+        output.push(text.lock().unwrap().clone());
+    }
+    Ok((output.remove(0), output.remove(0)))
+}
+
+fn append(path: std::path::PathBuf, text: &str) -> Result<()> {
+    OpenOptions::new().append(true).open(path)?.write_all(text.as_bytes())?;
+    Ok(())
+}
+
+#[test]
+fn follow_descriptor() -> Result<()> {
+    let run = start_follow(
+        &["-f", "log", "other"],
+        &[("log", "a\n"), ("other", "x\n")],
+    )?;
+    wait_stdout(&run, "x\n");
+    append(run.dir.join("log"), "b\n")?;
+    wait_stdout(&run, "b\n");
+    append(run.dir.join("other"), "y\n")?;
+    wait_stdout(&run, "y\n");
+    // a renamed file is still followed by descriptor.
+    fs::rename(run.dir.join("log"), run.dir.join("log.1"))?;
+    append(run.dir.join("log.1"), "c\n")?;
+    wait_stdout(&run, "c\n");
+    let (stdout, _) = finish_follow(run)?;
+    assert_eq!(
+        stdout,
+        "==> log <==\na\n\n==> other <==\nx\n\n==> log <==\nb\n\n\
+         ==> other <==\ny\n\n==> log <==\nc\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn follow_truncated() -> Result<()> {
+    let run = start_follow(&["-f", "log"], &[("log", "one\ntwo\n")])?;
+    wait_stdout(&run, "two\n");
+    fs::write(run.dir.join("log"), "")?;
+    wait_stderr(&run, "truncated");
+    append(run.dir.join("log"), "three\n")?;
+    wait_stdout(&run, "three\n");
+    let (stdout, stderr) = finish_follow(run)?;
+    assert_eq!(stdout, "one\ntwo\nthree\n");
+    assert_eq!(stderr, "log: file truncated\n");
+
+    Ok(())
+}
+
+#[test]
+fn follow_name_rotated() -> Result<()> {
+    let run = start_follow(&["-F", "log"], &[("log", "old\n")])?;
+    wait_stdout(&run, "old\n");
+    fs::rename(run.dir.join("log"), run.dir.join("log.1"))?;
+    wait_stderr(&run, "'log' has become inaccessible");
+    fs::write(run.dir.join("log"), "new\n")?;
+    wait_stdout(&run, "new\n");
+    append(run.dir.join("log"), "newer\n")?;
+    wait_stdout(&run, "newer\n");
+    let (stdout, stderr) = finish_follow(run)?;
+    assert_eq!(stdout, "old\nnew\nnewer\n");
+    assert!(stderr.contains("'log' has appeared; following new file"));
+
+    Ok(())
+}
+
+#[test]
+fn follow_name_retry_missing() -> Result<()> {
+    // With -F, a file that doesn't exist yet is picked up when it appears.
+    let run = start_follow(&["-F", "log"], &[])?;
+    wait_stderr(&run, "log: ");
+    fs::write(run.dir.join("log"), "hello\n")?;
+    wait_stdout(&run, "hello\n");
+    let (stdout, stderr) = finish_follow(run)?;
+    assert_eq!(stdout, "hello\n");
+    assert!(stderr.contains("'log' has appeared; following new file"));
+
+    Ok(())
+}
+
+#[test]
+fn follow_descriptor_retry_missing() -> Result<()> {
+    // --retry without --follow=name still waits for the first open, and
+    // then sticks with that file like GNU tail.
+    let run = start_follow(&["-f", "--retry", "log"], &[])?;
+    wait_stderr(&run, "log: ");
+    fs::write(run.dir.join("log"), "hello\n")?;
+    wait_stdout(&run, "hello\n");
+    fs::rename(run.dir.join("log"), run.dir.join("log.1"))?;
+    append(run.dir.join("log.1"), "again\n")?;
+    wait_stdout(&run, "again\n");
+    let (stdout, stderr) = finish_follow(run)?;
+    assert_eq!(stdout, "hello\nagain\n");
+    assert!(stderr.contains("warning: --retry only effective for the initial open"));
+    assert!(stderr.contains("'log' has appeared; following new file"));
+
+    Ok(())
+}
@@ -1,7 +1,12 @@
-use anyhow::Result;
-use clap::Parser;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use anyhow::{anyhow, Result};
+use clap::{Parser, ValueEnum};
+use serde_json::{Map, Value};
+use std::ffi::OsStr;
+use std::fs::{File, Metadata};
+use std::io::{self, BufRead, BufReader, Read};
+use std::os::fd::AsFd;
+use std::os::unix::ffi::OsStrExt;
+use unicode_width::UnicodeWidthChar;
 
 
 #[derive(Debug, Parser)]
@@ -9,7 +14,7 @@ use std::io::{self, BufRead, BufReader};
 /// Rust version of wc
 struct Args {
     /// Input file(s)
-    #[arg(value_name("FILE"), default_value("-"))]
+    #[arg(value_name("FILE"))]
     files: Vec<String>,
     /// Show line count
     #[arg(short('l'), long,)]
@@ -23,6 +28,29 @@ struct Args {
     /// Show character count
     #[arg(short('m'), long,)]
     chars: bool,
+    /// Show maximum line display width
+    #[arg(short('L'), long,)]
+    max_line_length: bool,
+    /// Read NUL-separated file names from F, or stdin if F is -
+    #[arg(value_name("F"), long, conflicts_with("files"))]
+    files0_from: Option<String>,
+    /// Pad every count to N columns instead of sizing them from the input
+    #[arg(value_name("N"), long)]
+    fixed_width: Option<usize>,
+    /// Print padded columns, or one record per file and a totals record as
+    /// JSON Lines, CSV or TSV
+    #[arg(value_name("FORMAT"), long, value_enum, default_value("table"))]
+    format: Format,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Table,
+    /// One JSON object per line.
+    Json,
+    Csv,
+    Tsv,
 }
 
 #[derive(Default, Debug, PartialEq)]
@@ -31,69 +59,234 @@ struct FileInfo {
     word_count: usize,
     byte_count: usize,
     char_count: usize,
+    max_line_length: usize,
  }
 
 
-fn open(filename: &str) -> Result<Box<dyn BufRead>> {
+fn open(filename: &OsStr) -> Result<Box<dyn BufRead>> {
     // Open stdin or a file for reading, depending on filename passed.
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+    if filename == "-" {
+        Ok(Box::new(BufReader::new(io::stdin())))
+    } else {
+        Ok(Box::new(BufReader::new(File::open(filename)?)))
+    }
+}
+
+
+fn metadata(filename: &OsStr) -> io::Result<Metadata> {
+    // Metadata for a file, or for whatever stdin is connected to.
+    if filename == "-" {
+        File::from(io::stdin().as_fd().try_clone_to_owned()?).metadata()
+    } else {
+        std::fs::metadata(filename)
     }
 }
 
 
-fn counter(mut  filename: impl BufRead) -> Result<FileInfo> {
+fn counter(mut file: impl BufRead) -> Result<FileInfo> {
     // read passed file and return a FileInfo struct containing the counts
     // of the various file elements.
 
-    let mut line = String::new();
+    let mut line = Vec::new();
     let mut file_counts = FileInfo{..Default::default()};
+    let mut in_word = false;
+    // display width of the current line so far.
+    let mut line_width = 0;
 
-    // using read_line to allow an accurate byte and character
-    // count, since it preserves line endings.
-    while let Ok(num_bytes) = filename.read_line(&mut line) {
+    // using read_until on raw bytes rather than read_line, which stops
+    // at the first byte that isn't valid UTF-8. A newline never occurs
+    // inside a multibyte character, so each line can be decoded by itself.
+    // Read errors are passed on rather than ending the count early.
+    loop {
+        let num_bytes = file.read_until(b'\n', &mut line)?;
         // break out of loop at end of file
-        if num_bytes == 0 { 
+        if num_bytes == 0 {
             break;
         }
 
-        file_counts.line_count += 1;
+        // only count newlines, like wc, so a last line without one
+        // doesn't add to the line count.
+        if line.ends_with(b"\n") {
+            file_counts.line_count += 1;
+        }
 
-        // return value of read_line is number of bytes read, so
+        // return value of read_until is number of bytes read, so
         // we can use it as the count here.
         file_counts.byte_count += num_bytes;
 
-        // split_whitespace rather than split ensures all 
-        //whitespace is treated as a separator.
-        file_counts.word_count += line.split_whitespace().count();
+        // bytes that aren't valid UTF-8 count towards -c but, as in GNU
+        // wc, aren't characters and leave words alone.
+        for chunk in line.utf8_chunks() {
+            for c in chunk.valid().chars() {
+                file_counts.char_count += 1;
+                // as in GNU wc, a word is a run of printable characters
+                // between separators; anything else neither starts nor
+                // ends one.
+                if is_word_separator(c) {
+                    in_word = false;
+                } else if is_printable(c) && !in_word {
+                    in_word = true;
+                    file_counts.word_count += 1;
+                }
 
-        file_counts.char_count += line.chars().count();
+                // like GNU wc, carriage returns and form feeds end a line
+                // as far as its width goes, and tabs go to the next
+                // multiple of 8. Characters that can't be printed take no
+                // room, nor do invalid sequences.
+                match c {
+                    '\n' | '\r' | '\x0c' => {
+                        file_counts.max_line_length =
+                            file_counts.max_line_length.max(line_width);
+                        line_width = 0;
+                    }
+                    '\t' => line_width += 8 - line_width % 8,
+                    _ if is_printable(c) => line_width += c.width().unwrap_or(0),
+                    _ => {}
+                }
+            }
+        }
 
         line.clear();
     }
+    file_counts.max_line_length = file_counts.max_line_length.max(line_width);
 
     Ok(file_counts)
 }
 
 
-fn format_output(count: usize, show: bool) -> std::string::String{
-    // format individual file element count for display in report or suppress
-    // it, depending on flag.
-    if show {
-        format!("{:>8}", count)
+fn is_word_separator(c: char) -> bool {
+    // The characters GNU wc splits words on in a UTF-8 locale: what glibc
+    // calls white space, plus the no-break spaces.
+    matches!(c,
+        '\t' | '\n' | '\x0b' | '\x0c' | '\r' | ' '
+        | '\u{a0}' | '\u{1680}' | '\u{2000}'..='\u{200a}' | '\u{202f}'
+        | '\u{205f}' | '\u{2060}' | '\u{3000}')
+}
+
+
+fn is_printable(c: char) -> bool {
+    // Control characters and noncharacters can't be part of a word.
+    let code = c as u32;
+    !(c.is_control() || (0xfdd0..=0xfdef).contains(&code)
+      || code & 0xfffe == 0xfffe)
+}
+
+
+fn number_width(names: &[Vec<u8>], single_count: bool) -> usize {
+    // Work out the column width the way GNU wc does: enough digits for the
+    // combined size of the regular files, since no count can be bigger,
+    // but at least 7 if any input is something else whose size can't be
+    // known in advance. Files that can't be examined don't count, and a
+    // single count of a single file isn't padded at all.
+    if names.is_empty() || (names.len() == 1 && single_count) {
+        return 1;
+    }
+    let mut minimum = 1;
+    let mut total: u64 = 0;
+    for name in names {
+        match metadata(OsStr::from_bytes(name)) {
+            Err(_) => {}
+            Ok(meta) if meta.is_file() => total += meta.len(),
+            Ok(_) => minimum = 7,
+        }
+    }
+    total.to_string().len().max(minimum)
+}
+
+
+fn selected(counts: &FileInfo, args: &Args) -> Vec<(&'static str, usize)> {
+    // the counts chosen by the flags, in report order, named after their
+    // FileInfo fields for the structured formats.
+    [
+        ("line_count", counts.line_count, args.lines),
+        ("word_count", counts.word_count, args.words),
+        ("byte_count", counts.byte_count, args.bytes),
+        ("char_count", counts.char_count, args.chars),
+        ("max_line_length", counts.max_line_length, args.max_line_length),
+    ]
+        .into_iter()
+        .filter(|(_, _, show)| *show)
+        .map(|(name, count, _)| (name, count))
+        .collect()
+}
+
+
+fn format_output(counts: &FileInfo, args: &Args, width: usize) -> String {
+    // format the counts selected by the flags for one row of the report,
+    // each right-aligned in a column of the given width and separated by
+    // single spaces, as GNU wc does.
+    selected(counts, args)
+        .iter()
+        .map(|(_, count)| format!("{count:>width$}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+
+fn print_record(args: &Args, path: Option<&str>,
+                result: Result<&FileInfo, &str>) {
+    // print one file's counts, or why it couldn't be read, as a JSON, CSV or
+    // TSV record. The counts of a file that couldn't be read are null, or
+    // empty fields, as is the error for one that could. The totals have no
+    // path and are marked as such, so a file called "total" can't be taken
+    // for them.
+    let empty = FileInfo::default();
+    let counts = selected(result.unwrap_or(&empty), args);
+    let error = result.err();
+    if args.format == Format::Json {
+        let mut record = Map::new();
+        record.insert("path".into(), path.into());
+        record.insert("total".into(), path.is_none().into());
+        for (name, count) in counts {
+            let value = match error {
+                None => Value::from(count),
+                Some(_) => Value::Null,
+            };
+            record.insert(name.into(), value);
+        }
+        record.insert("error".into(), error.into());
+        println!("{}", Value::Object(record));
     } else {
-        format!("")
+        let mut fields = vec![
+            path.unwrap_or_default().to_string(),
+            path.is_none().to_string(),
+        ];
+        for (_, count) in counts {
+            fields.push(match error {
+                None => count.to_string(),
+                Some(_) => String::new(),
+            });
+        }
+        fields.push(error.unwrap_or_default().to_string());
+        print_row(args.format, &fields);
     }
 }
 
 
+fn print_row(format: Format, fields: &[String]) {
+    // write a CSV or TSV row. CSV fields are quoted when they need to be;
+    // TSV has no quoting, so tabs, line breaks and backslashes are escaped
+    // with backslashes instead.
+    let fields: Vec<String> = fields.iter().map(|field| match format {
+        Format::Csv if field.contains([',', '"', '\n', '\r']) => {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        }
+        Format::Tsv => field.replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r"),
+        _ => field.clone(),
+    }).collect();
+    println!("{}", fields.join(if format == Format::Tsv {"\t"} else {","}));
+}
+
+
 fn run(mut args: Args) -> Result<()> {
     // if the user doesn't set any flags, the default is to display 
     // information on lines, words, and bytes.  Set flags accorddingly.
-    if [args.lines, args.words, args.bytes, args.chars]
+    if [args.lines, args.words, args.bytes, args.chars, args.max_line_length]
         .iter()
-        .all(|v| *v == false)
+        .all(|v| !*v)
         {
             args.lines = true;
             args.words = true;
@@ -102,39 +295,121 @@ fn run(mut args: Args) -> Result<()> {
 
     let mut totals = FileInfo {..Default::default()};
 
-    for filename in &args.files {
-        match open(&filename) {
-            // If there is a problem opening the file, note it and move on.
-            Err(err) => eprintln!("{filename}: {err}"),
-            Ok(current_file) => {
-                let current_counts: FileInfo = counter(current_file)?;
-
-                println!("{}{}{}{}{}", 
-                    format_output(current_counts.line_count, args.lines),
-                    format_output(current_counts.word_count, args.words),
-                    format_output(current_counts.byte_count, args.bytes),
-                    format_output(current_counts.char_count, args.chars),
-                    if filename == "-" {
-                        "".to_string()
-                    } else {
-                        format!(" {filename}")
+    // stdin is only named in the report when it was asked for as '-'.
+    let unnamed = args.files0_from.is_none() && args.files.is_empty();
+    if unnamed {
+        args.files.push("-".to_string());
+    }
+
+    let single_count = [args.lines, args.words, args.bytes, args.chars,
+                        args.max_line_length]
+        .iter()
+        .filter(|v| **v)
+        .count() == 1;
+    let sized = |names: &[Vec<u8>]| {
+        args.fixed_width.unwrap_or_else(|| number_width(names, single_count))
+    };
+    // file names come from the command line or, with --files0-from, from
+    // a list. A list that is a regular file of reasonable size is read up
+    // front, as GNU wc does, so the column width can take the files into
+    // account; any other list is read one name at a time so that any
+    // number of them can be handled.
+    let (names, width): (Box<dyn Iterator<Item = io::Result<Vec<u8>>>>, usize) =
+        match &args.files0_from {
+            Some(source) => {
+                let mut list = open(source.as_ref())
+                    .map_err(|e| anyhow!("{source}: {e}"))?;
+                let small = metadata(source.as_ref())
+                    .is_ok_and(|m| m.is_file() && m.len() <= 10 * 1024 * 1024);
+                if small {
+                    let mut bytes = Vec::new();
+                    list.read_to_end(&mut bytes)
+                        .map_err(|e| anyhow!("{source}: {e}"))?;
+                    let mut names: Vec<Vec<u8>> =
+                        bytes.split(|&b| b == b'\0').map(<[u8]>::to_vec).collect();
+                    // the list ends with a terminator, not an empty name.
+                    if names.last().is_some_and(Vec::is_empty) {
+                        names.pop();
                     }
-                );
+                    let width = sized(&names);
+                    (Box::new(names.into_iter().map(Ok)), width)
+                } else {
+                    (Box::new(list.split(b'\0')), args.fixed_width.unwrap_or(1))
+                }
+            }
+            None => {
+                let names: Vec<Vec<u8>> =
+                    args.files.iter().map(|f| f.clone().into_bytes()).collect();
+                let width = sized(&names);
+                (Box::new(names.into_iter().map(Ok)), width)
+            }
+        };
+    let mut num_files = 0;
+
+    if matches!(args.format, Format::Csv | Format::Tsv) {
+        let mut header = vec!["path".to_string(), "total".to_string()];
+        for (name, _) in selected(&totals, &args) {
+            header.push(name.to_string());
+        }
+        header.push("error".to_string());
+        print_row(args.format, &header);
+    }
+
+    for name in names {
+        let name = name?;
+        num_files += 1;
+        let filename = String::from_utf8_lossy(&name);
+        if let Some(source) = &args.files0_from {
+            if name.is_empty() {
+                eprintln!("{source}:{num_files}: invalid zero-length file name");
+                continue;
+            }
+            if source == "-" && filename == "-" {
+                eprintln!("when reading file names from stdin, no file name \
+                           of '-' allowed");
+                continue;
+            }
+        }
+        match open(OsStr::from_bytes(&name)).and_then(counter) {
+            // If there is a problem reading the file, note it and move on.
+            // The structured formats report it as a record as well.
+            Err(err) => {
+                eprintln!("{filename}: {err}");
+                if args.format != Format::Table {
+                    print_record(&args, Some(&filename), Err(&err.to_string()));
+                }
+            }
+            Ok(current_counts) => {
+                if args.format == Format::Table {
+                    println!("{}{}",
+                        format_output(&current_counts, &args, width),
+                        if unnamed {
+                            "".to_string()
+                        } else {
+                            format!(" {filename}")
+                        }
+                    );
+                } else {
+                    print_record(&args, Some(&filename), Ok(&current_counts));
+                }
 
                 totals.line_count += current_counts.line_count;
                 totals.word_count += current_counts.word_count;
                 totals.byte_count += current_counts.byte_count;
                 totals.char_count += current_counts.char_count;
+                // the total row gives the longest line of all, as GNU
+                // wc does, rather than a sum.
+                totals.max_line_length =
+                    totals.max_line_length.max(current_counts.max_line_length);
             },
         };
     }
-    if args.files.len() > 1 {
-        println!("{}{}{}{} total", 
-            format_output(totals.line_count, args.lines),
-            format_output(totals.word_count, args.words),
-            format_output(totals.byte_count, args.bytes),
-            format_output(totals.char_count, args.chars),
-        );
+    // the structured formats always end with the totals, so there's one
+    // shape of output to parse however many files there were.
+    if args.format != Format::Table {
+        print_record(&args, None, Ok(&totals));
+    } else if num_files > 1 {
+        println!("{} total", format_output(&totals, &args, width));
     }
 
     Ok(())
@@ -9,6 +9,9 @@ const PRG: &str = "wcr";
 const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const ATLAMAL: &str = "tests/inputs/atlamal.txt";
+const INVALID: &str = "tests/inputs/invalid.txt";
+const WIDE: &str = "tests/inputs/wide.txt";
+const FILES0: &str = "tests/inputs/files0.txt";
 
 // --------------------------------------------------
 fn gen_bad_file() -> String {
@@ -218,3 +221,292 @@ fn test_all_words_lines() -> Result<()> {
 fn test_all_bytes_lines() -> Result<()> {
     run(&["-cl", EMPTY, FOX, ATLAMAL], "tests/expected/all.cl.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn invalid() -> Result<()> {
+    // Counting carries on past bytes that aren't valid UTF-8, and the
+    // unterminated last line isn't counted, matching GNU wc.
+    run(&[INVALID], "tests/expected/invalid.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn invalid_chars() -> Result<()> {
+    // Bytes that aren't valid UTF-8 aren't characters, as in GNU wc.
+    run(&["-m", INVALID], "tests/expected/invalid.txt.m.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn invalid_stdin() -> Result<()> {
+    let input = fs::read(INVALID)?;
+    let expected = fs::read_to_string("tests/expected/invalid.txt.stdin.out")?;
+
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(stdout, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn words_like_gnu() -> Result<()> {
+    // No-break spaces separate words; control characters and stray bytes
+    // neither separate nor make words.
+    let cases: &[(&[u8], &str)] = &[
+        (b"a\xc2\xa0b", "2"),
+        (b"a\xe2\x80\x83b", "2"),
+        (b"a\x01b", "1"),
+        (b"a\xffb", "1"),
+        (b"\x01 \xff", "0"),
+        (b"one\ttwo\x0bthree\x0cfour\rfive", "5"),
+    ];
+    for (input, words) in cases {
+        let output = Command::cargo_bin(PRG)?
+            .arg("-w")
+            .write_stdin(*input)
+            .output()?;
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout)?.trim(), *words);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn wide_max_line_length() -> Result<()> {
+    // Tabs stop every 8 columns, CJK characters take 2 and combining
+    // marks none.
+    run(&["-L", WIDE], "tests/expected/wide.txt.L.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_max_line_length() -> Result<()> {
+    // The total is the longest line of all, not a sum.
+    run(&["-L", WIDE, FOX, ATLAMAL], "tests/expected/all.L.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_lines_max_line_length() -> Result<()> {
+    run(&["-lL", EMPTY, FOX, ATLAMAL], "tests/expected/all.lL.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn max_line_length_carriage_return() -> Result<()> {
+    // A carriage return starts the width over, as in GNU wc.
+    Command::cargo_bin(PRG)?
+        .arg("--max-line-length")
+        .write_stdin("abcdef\rxy\nabc")
+        .assert()
+        .success()
+        .stdout("6\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from() -> Result<()> {
+    run(&["--files0-from", FILES0], "tests/expected/all.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from_stdin() -> Result<()> {
+    // Names read one at a time can't be sized in advance, so the counts
+    // aren't padded.
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from=-", "-lwm"])
+        .write_stdin(fs::read(FILES0)?)
+        .assert()
+        .success()
+        .stdout(format!("0 0 0 {EMPTY}\n1 9 48 {FOX}\n4 29 159 {ATLAMAL}\n\
+                         5 38 207 total\n"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from_many() -> Result<()> {
+    // More names than would comfortably fit on a command line.
+    let names = format!("{FOX}\0").repeat(20_000);
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from=-", "-l"])
+        .write_stdin(names)
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with("\n20000 total\n"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from_bad_names() -> Result<()> {
+    // Problems with single names are reported and skipped.
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from=-", "-l"])
+        .write_stdin(format!("{FOX}\0\0-\0nope\0{FOX}"))
+        .assert()
+        .success()
+        .stdout(format!("1 {FOX}\n1 {FOX}\n2 total\n"))
+        .stderr(predicate::str::contains("-:2: invalid zero-length file name"))
+        .stderr(predicate::str::contains(
+            "when reading file names from stdin, no file name of '-' allowed",
+        ))
+        .stderr(predicate::str::contains("nope: "));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_files0_from_and_files() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from", FILES0, FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the argument '--files0-from <F>' cannot be used with '[FILE]...'",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_files0_from_missing() -> Result<()> {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from", &bad])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(format!("{bad}: .* [(]os error 2[)]"))?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_named_when_given() -> Result<()> {
+    // Like GNU wc, stdin only gets a name when asked for as '-', and
+    // anything that isn't a regular file widens the columns to 7.
+    Command::cargo_bin(PRG)?
+        .args([FOX, "-"])
+        .write_stdin("a b\n")
+        .assert()
+        .success()
+        .stdout(format!("      1       9      48 {FOX}\n\
+                         \x20     1       2       4 -\n\
+                         \x20     2      11      52 total\n"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn fixed_width() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--fixed-width=5", "-lc", FOX])
+        .assert()
+        .success()
+        .stdout(format!("    1    48 {FOX}\n"));
+    Command::cargo_bin(PRG)?
+        .args(["--fixed-width", "0"])
+        .write_stdin("a b\n")
+        .assert()
+        .success()
+        .stdout("1 2 4\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn format_json() -> Result<()> {
+    run(&["--format=json", EMPTY, FOX, ATLAMAL], "tests/expected/all.json.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn format_csv() -> Result<()> {
+    run(&["--format=csv", EMPTY, FOX, ATLAMAL], "tests/expected/all.csv.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn format_tsv() -> Result<()> {
+    run(&["--format", "tsv", EMPTY, FOX, ATLAMAL], "tests/expected/all.tsv.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn format_records_errors() -> Result<()> {
+    // A file that can't be read gets a record of its own, with no counts,
+    // and is still reported on stderr.
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["--format=json", "-l", &bad, "tests/inputs"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(format!(
+            "^[{{]\"error\":\".* [(]os error 2[)]\",\"line_count\":null,\
+             \"path\":\"{bad}\",\"total\":false[}}]\n\
+             [{{]\"error\":\".* [(]os error 21[)]\",\"line_count\":null,\
+             \"path\":\"tests/inputs\",\"total\":false[}}]\n\
+             [{{]\"error\":null,\"line_count\":0,\"path\":null,\
+             \"total\":true[}}]\n$"
+        ))?)
+        .stderr(predicate::str::contains(format!("{bad}: ")));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn format_escapes_paths() -> Result<()> {
+    // Paths are quoted for CSV and backslash-escaped for TSV; stdin is '-'.
+    let dir = std::env::temp_dir().join(format!("wcr-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let path = dir.join("a,\"b\"\tc");
+    fs::write(&path, "x\n")?;
+    let name = path.to_str().unwrap();
+    let dir_name = dir.to_str().unwrap();
+    Command::cargo_bin(PRG)?
+        .args(["--format=csv", "-c", name, "-"])
+        .write_stdin("yz\n")
+        .assert()
+        .success()
+        .stdout(format!("path,total,byte_count,error\n\
+                         \"{dir_name}/a,\"\"b\"\"\tc\",false,2,\n\
+                         -,false,3,\n,true,5,\n"));
+    Command::cargo_bin(PRG)?
+        .args(["--format=tsv", "-c", name])
+        .assert()
+        .success()
+        .stdout(format!("path\ttotal\tbyte_count\terror\n\
+                         {dir_name}/a,\"b\"\\tc\tfalse\t2\t\n\ttrue\t2\t\n"));
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn format_total_not_a_file() -> Result<()> {
+    // The totals record can't be mistaken for a file called "total".
+    let dir = std::env::temp_dir().join(format!("wcr-total-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join("total"), "a b\n")?;
+    Command::cargo_bin(PRG)?
+        .current_dir(&dir)
+        .args(["--format=json", "-l", "total"])
+        .assert()
+        .success()
+        .stdout("{\"error\":null,\"line_count\":1,\"path\":\"total\",\
+                 \"total\":false}\n\
+                 {\"error\":null,\"line_count\":1,\"path\":null,\
+                 \"total\":true}\n");
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}